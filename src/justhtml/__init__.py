@@ -12,6 +12,7 @@ from .sanitize import (
 from .selector import SelectorError, matches, query
 from .serialize import to_html, to_test_format
 from .stream import stream
+from .tokenizer import has_markup
 from .tokens import ParseError
 from .transforms import (
     CollapseWhitespace,
@@ -57,6 +58,7 @@ __all__ = [
     "UrlPolicy",
     "UrlProxy",
     "UrlRule",
+    "has_markup",
     "matches",
     "query",
     "stream",
@@ -224,6 +224,8 @@ def decode_entities_in_text(
     text: str,
     in_attribute: bool = False,
     report_error: Callable[[str], None] | None = None,
+    *,
+    require_semicolon: bool = False,
 ) -> str:
     """Decode all HTML entities in text.
 
@@ -235,6 +237,7 @@ def decode_entities_in_text(
     Args:
         text: Input text potentially containing entities
         in_attribute: Whether this is attribute value (stricter rules for legacy entities)
+        require_semicolon: Leave references without a trailing semicolon, like &amp or &#60, undecoded
 
     Returns:
         Text with entities decoded
@@ -276,7 +279,7 @@ def decode_entities_in_text(
             has_semicolon = j < length and text[j] == ";"
             digit_text = text[digit_start:j]
 
-            if digit_text:
+            if digit_text and (has_semicolon or not require_semicolon):
                 if report_error is not None and not has_semicolon:
                     report_error("missing-semicolon-after-character-reference")
                 result.append(decode_numeric_entity(digit_text, is_hex=is_hex, report_error=report_error))
@@ -306,6 +309,10 @@ def decode_entities_in_text(
             result.append(NAMED_ENTITIES[entity_name])
             i = j + 1
             continue
+        if require_semicolon:
+            result.append("&")
+            i += 1
+            continue
         # If semicolon present but no exact match, allow legacy prefix match in text
         if has_semicolon and not in_attribute:
             best_match: str | None = None
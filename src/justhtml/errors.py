@@ -47,6 +47,7 @@ def generate_error_message(code: str, tag_name: str | None = None) -> str:
         "eof-in-comment": "Unexpected end of file in comment",
         "abrupt-closing-of-empty-comment": "Comment ended abruptly with -->",
         "incorrectly-closed-comment": "Comment ended with --!> instead of -->",
+        "comment-too-long": "Comment truncated: it exceeds the maximum comment length",
         # Tag errors
         "eof-in-tag": "Unexpected end of file in tag",
         "eof-before-tag-name": "Unexpected end of file before tag name",
@@ -54,8 +55,11 @@ def generate_error_message(code: str, tag_name: str | None = None) -> str:
         "invalid-first-character-of-tag-name": "Invalid first character of tag name",
         "unexpected-question-mark-instead-of-tag-name": "Unexpected ? instead of tag name",
         "unexpected-character-after-solidus-in-tag": "Unexpected character after / in tag",
+        "end-tag-with-attributes": "End tag has attributes, which are ignored",
+        "end-tag-with-trailing-solidus": "End tag has a trailing /, which is ignored",
         # Attribute errors
         "duplicate-attribute": "Duplicate attribute name",
+        "too-many-attributes": "Attribute dropped: tag exceeds the maximum number of attributes",
         "missing-attribute-value": "Missing attribute value",
         "unexpected-character-in-attribute-name": "Unexpected character in attribute name",
         "unexpected-character-in-unquoted-attribute-value": "Unexpected character in unquoted attribute value",
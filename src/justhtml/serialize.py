@@ -64,52 +64,67 @@ def _serializer_minimize_attr_value(name: str, value: str | None, minimize_boole
     return value.lower() == name
 
 
-def serialize_start_tag(
-    name: str,
+def serialize_attrs(
     attrs: dict[str, str | None] | None,
     *,
     quote_attr_values: bool = True,
     minimize_boolean_attributes: bool = True,
     quote_char: str | None = None,
     escape_lt_in_attrs: bool = False,
-    use_trailing_solidus: bool = False,
-    is_void: bool = False,
 ) -> str:
-    attrs = attrs or {}
-    parts: list[str] = ["<", name]
-    if attrs:
-        for key, value in attrs.items():
-            if _serializer_minimize_attr_value(key, value, minimize_boolean_attributes):
-                parts.extend([" ", key])
-                continue
-
-            if value is None:
-                parts.extend([" ", key, '=""'])
-                continue
+    """Serialize attributes as they appear in a start tag, each prefixed with a space."""
+    if not attrs:
+        return ""
+    parts: list[str] = []
+    for key, value in attrs.items():
+        if _serializer_minimize_attr_value(key, value, minimize_boolean_attributes):
+            parts.extend([" ", key])
+            continue
 
-            # value is guaranteed to be a string here because attrs is dict[str, str | None]
-            value_str = value
-            if value_str == "":
-                parts.extend([" ", key, '=""'])
-                continue
+        if value is None:
+            parts.extend([" ", key, '=""'])
+            continue
 
-            if not quote_attr_values and _can_unquote_attr_value(value_str):
-                escaped = value_str.replace("&", "&amp;")
-                if escape_lt_in_attrs:
-                    escaped = escaped.replace("<", "&lt;")
-                parts.extend([" ", key, "=", escaped])
-            else:
-                quote = _choose_attr_quote(value_str, quote_char)
-                escaped = _escape_attr_value(value_str, quote, escape_lt_in_attrs=escape_lt_in_attrs)
-                parts.extend([" ", key, "=", quote, escaped, quote])
+        # value is guaranteed to be a string here because attrs is dict[str, str | None]
+        value_str = value
+        if value_str == "":
+            parts.extend([" ", key, '=""'])
+            continue
 
-    if use_trailing_solidus and is_void:
-        parts.append(" />")
-    else:
-        parts.append(">")
+        if not quote_attr_values and _can_unquote_attr_value(value_str):
+            escaped = value_str.replace("&", "&amp;")
+            if escape_lt_in_attrs:
+                escaped = escaped.replace("<", "&lt;")
+            parts.extend([" ", key, "=", escaped])
+        else:
+            quote = _choose_attr_quote(value_str, quote_char)
+            escaped = _escape_attr_value(value_str, quote, escape_lt_in_attrs=escape_lt_in_attrs)
+            parts.extend([" ", key, "=", quote, escaped, quote])
     return "".join(parts)
 
 
+def serialize_start_tag(
+    name: str,
+    attrs: dict[str, str | None] | None,
+    *,
+    quote_attr_values: bool = True,
+    minimize_boolean_attributes: bool = True,
+    quote_char: str | None = None,
+    escape_lt_in_attrs: bool = False,
+    use_trailing_solidus: bool = False,
+    is_void: bool = False,
+) -> str:
+    serialized_attrs = serialize_attrs(
+        attrs,
+        quote_attr_values=quote_attr_values,
+        minimize_boolean_attributes=minimize_boolean_attributes,
+        quote_char=quote_char,
+        escape_lt_in_attrs=escape_lt_in_attrs,
+    )
+    end = " />" if use_trailing_solidus and is_void else ">"
+    return f"<{name}{serialized_attrs}{end}"
+
+
 def serialize_end_tag(name: str) -> str:
     return f"</{name}>"
 
@@ -1,16 +1,26 @@
 from __future__ import annotations
 
+import re
+from bisect import bisect_right
 from typing import TYPE_CHECKING, Any
 
 if TYPE_CHECKING:
-    from collections.abc import Generator
+    from collections.abc import Generator, Iterable, Iterator, Sequence
 
+from .constants import VOID_ELEMENTS
 from .encoding import decode_html
-from .tokenizer import Tokenizer
-from .tokens import CommentToken, DoctypeToken, Tag
+from .tokenizer import Tokenizer, TokenizerOpts
+from .tokens import CharacterTokens, CommentToken, DoctypeToken, Tag
 
-# Type alias for stream events
+_WHITESPACE_RUN_PATTERN = re.compile(r"[ \t\n\f]+")
+_NON_TEXT_ELEMENTS = frozenset(("script", "style"))
+
+# Type aliases for stream events
 StreamEvent = tuple[str, Any]
+SpannedStreamEvent = tuple[str, Any, tuple[int, int]]
+
+# Buffered event: (event_type, data, start, end)
+_SinkEvent = tuple[str, Any, int, int]
 
 
 class _DummyNode:
@@ -20,14 +30,30 @@ class _DummyNode:
 class StreamSink:
     """A sink that buffers tokens for the stream API."""
 
-    tokens: list[StreamEvent]
+    tokens: list[_SinkEvent]
     open_elements: list[_DummyNode]
+    tokenizer: Tokenizer | None
 
     def __init__(self) -> None:
         self.tokens = []
         self.open_elements = []  # Required by tokenizer for rawtext checks
+        self.tokenizer = None
+
+    def _span(self) -> tuple[int, int]:
+        # Called while the tokenizer is emitting: the token runs from its '<' to the current position.
+        tokenizer = self.tokenizer
+        if tokenizer is None:
+            return (-1, -1)
+        return (tokenizer.current_token_start_pos, tokenizer.pos)
 
-    def process_token(self, token: Tag | CommentToken | DoctypeToken | Any) -> int:
+    def _text_span(self) -> tuple[int, int]:
+        # Called while the tokenizer is flushing text: it records where the run came from.
+        tokenizer = self.tokenizer
+        if tokenizer is None:
+            return (-1, -1)
+        return (tokenizer.text_start_pos, tokenizer.text_end_pos)
+
+    def process_token(self, token: Tag | CommentToken | DoctypeToken | CharacterTokens | Any) -> int:
         # Tokenizer reuses token objects, so we must copy data
         if isinstance(token, Tag):
             # Copy tag data
@@ -35,6 +61,7 @@ class StreamSink:
                 (
                     "start" if token.kind == Tag.START else "end",
                     (token.name, token.attrs.copy()) if token.kind == Tag.START else token.name,
+                    *self._span(),
                 )
             )
             # Maintain open_elements stack for tokenizer's rawtext checks
@@ -50,58 +77,361 @@ class StreamSink:
                 # (it's an unmatched end tag at the root level)
 
         elif isinstance(token, CommentToken):
-            self.tokens.append(("comment", token.data))
+            self.tokens.append(("comment", token.data, *self._span()))
 
         elif isinstance(token, DoctypeToken):
             dt = token.doctype
-            self.tokens.append(("doctype", (dt.name, dt.public_id, dt.system_id)))
+            self.tokens.append(("doctype", (dt.name, dt.public_id, dt.system_id), *self._span()))
+
+        elif isinstance(token, CharacterTokens):
+            # An unfinished tag at EOF (e.g. "<div") is emitted back as text
+            self.tokens.append(("text", token.data, *self._span()))
 
         return 0  # TokenSinkResult.Continue
 
     def process_characters(self, data: str) -> None:
         """Handle character data from tokenizer."""
-        self.tokens.append(("text", data))
+        self.tokens.append(("text", data, *self._text_span()))
+
+
+class _DiscardSink:
+    """A sink that drops every token, for passes that only need tokenizer state or errors."""
+
+    open_elements: list[_DummyNode]
+
+    def __init__(self) -> None:
+        self.open_elements = []
+
+    def process_token(self, token: Any) -> int:
+        return 0
+
+    def process_characters(self, data: str) -> None:
+        pass
+
+
+class _TagBalanceSink(_DiscardSink):
+    """Counts start tags minus end tags per name, skipping void elements and self-closing start tags."""
+
+    balance: dict[str, int]
+    void_elements: frozenset[str]
+
+    def __init__(self, void_elements: frozenset[str]) -> None:
+        super().__init__()
+        self.balance = {}
+        self.void_elements = void_elements
+
+    def process_token(self, token: Any) -> int:
+        if isinstance(token, Tag) and token.name not in self.void_elements:
+            if token.kind == Tag.END:
+                delta = -1
+            else:
+                delta = 0 if token.self_closing else 1
+            self.balance[token.name] = self.balance.get(token.name, 0) + delta
+        return 0
+
+
+class Stream:
+    """Iterator over stream events, returned by stream()."""
+
+    __slots__ = ("_consumed", "_events", "_last_event_type", "_pushed", "_spans", "_split_lines", "_tokenizer")
+
+    _consumed: int
+    _events: Generator[SpannedStreamEvent, None, None]
+    _last_event_type: str | None
+    _pushed: list[SpannedStreamEvent]
+    _spans: bool
+    _split_lines: bool
+    _tokenizer: Tokenizer
+
+    def __init__(
+        self,
+        html: str,
+        *,
+        spans: bool = False,
+        tokenizer_opts: TokenizerOpts | None = None,
+        stop_at_tags: Iterable[str] | None = None,
+        split_lines: bool = False,
+        verify_coverage: bool = False,
+    ) -> None:
+        sink = StreamSink()
+        tokenizer = Tokenizer(sink, tokenizer_opts)
+        sink.tokenizer = tokenizer
+        tokenizer.initialize(html)
+        self._tokenizer = tokenizer
+        self._spans = spans
+        self._split_lines = split_lines
+        self._consumed = 0
+        self._pushed = []
+        self._last_event_type = None
+        self._events = self._generate(sink, frozenset(stop_at_tags or ()))
+        if verify_coverage:
+            self._events = self._check_coverage(self._events)
+
+    def __iter__(self) -> Stream:
+        return self
+
+    def __next__(self) -> tuple[Any, ...]:
+        if self._pushed:
+            event, data, span = self._pushed.pop()
+        else:
+            event, data, span = next(self._events)
+            self._consumed = span[1]
+        self._last_event_type = event
+        if self._spans:
+            return (event, data, span)
+        return (event, data)
+
+    @property
+    def last_event_type(self) -> str | None:
+        """The type of the last event yielded ("start", "text", ...), or None before the first one."""
+        return self._last_event_type
+
+    def push(self, event: str, data: Any, span: tuple[int, int] = (-1, -1)) -> None:
+        """Yield (event, data) next, before the rest of the input. The last event pushed comes out first."""
+        self._pushed.append((event, data, span))
+
+    def remaining(self) -> str:
+        """Return the input after the last event yielded so far."""
+        return self._tokenizer.buffer[self._consumed :]
+
+    def _generate(self, sink: StreamSink, stop_at_tags: frozenset[str]) -> Generator[SpannedStreamEvent, None, None]:
+        tokenizer = self._tokenizer
+        # Text is coalesced across steps so each run of text is a single event. Runs are
+        # only joined when they touch: markup dropped between them leaves a gap.
+        text_buffer: list[str] = []
+        text_start = text_end = 0
+        while True:
+            # Run one step of the tokenizer
+            is_eof = tokenizer.step()
+
+            # Yield any tokens produced by this step
+            for event, data, start, end in sink.tokens:
+                if text_buffer and (event != "text" or start != text_end):
+                    text = "".join(text_buffer)
+                    text_buffer.clear()
+                    yield from self._text_events(text, text_start, text_end)
+                if event == "text":
+                    if not text_buffer:
+                        text_start = start
+                    text_buffer.append(data)
+                    text_end = end
+                    continue
+                yield (event, data, (start, end))
+                if event == "start" and data[0] in stop_at_tags:
+                    # remaining() picks up right after the stop tag
+                    return
+            sink.tokens.clear()
+
+            if is_eof:
+                break
+
+        if text_buffer:
+            yield from self._text_events("".join(text_buffer), text_start, text_end)
+
+    def _check_coverage(self, events: Iterator[SpannedStreamEvent]) -> Generator[SpannedStreamEvent, None, None]:
+        # Each span must start where the previous one ended, and the last one must end
+        # at the end of the input unless stop_at_tags ended the stream early.
+        tokenizer = self._tokenizer
+        covered = 0
+        for event in events:
+            start, end = event[2]
+            if start != covered:
+                raise RuntimeError(f"Span coverage broken: {event[0]!r} event starts at {start}, expected {covered}")
+            covered = end
+            yield event
+        if tokenizer.pos >= tokenizer.length and covered != tokenizer.length:
+            raise RuntimeError(f"Span coverage broken: events end at {covered} of {tokenizer.length}")
+
+    def _text_events(self, text: str, start: int, end: int) -> Iterator[SpannedStreamEvent]:
+        newline_count = text.count("\n")
+        if not self._split_lines or newline_count == 0 or (newline_count == 1 and text[-1] == "\n"):
+            yield ("text", text, (start, end))
+            return
+
+        # Map each line to the source by its newline. Character references such as
+        # &#10; decode to newlines that are not in the source; those lines get the whole span.
+        buffer = self._tokenizer.buffer
+        breaks: list[int] = []
+        newline = buffer.find("\n", start, end)
+        while newline != -1:
+            breaks.append(newline + 1)
+            newline = buffer.find("\n", newline + 1, end)
+        aligned = len(breaks) == newline_count
+
+        line_start = start
+        for index, line in enumerate(text.split("\n")):
+            if index == newline_count:
+                # Text after the last newline, if any
+                if line:
+                    yield ("text", line, (line_start if aligned else start, end))
+                break
+            if not aligned:
+                yield ("text", line + "\n", (start, end))
+                continue
+            line_end = breaks[index]
+            yield ("text", line + "\n", (line_start, line_end))
+            line_start = line_end
 
 
 def stream(
     html: str | bytes | bytearray | memoryview,
     *,
     encoding: str | None = None,
-) -> Generator[StreamEvent, None, None]:
+    spans: bool = False,
+    tokenizer_opts: TokenizerOpts | None = None,
+    stop_at_tags: Iterable[str] | None = None,
+    split_lines: bool = False,
+    verify_coverage: bool = False,
+) -> Stream:
     """
     Stream HTML events from the given HTML string.
     Yields tuples of (event_type, data).
+
+    With spans=True, yields (event_type, data, (start, end)) instead. Offsets are
+    half-open and index the decoded input after BOM removal and newline
+    normalization. A text span covers the source the text was read from, so
+    markup dropped without an event, such as "</>", is outside every span; text
+    on both sides of it comes out as two events.
+
+    With stop_at_tags, iteration ends right after the first start tag with one of
+    those names; Stream.remaining() returns the input that was not tokenized.
+
+    With split_lines=True, text events are split after every newline, so each
+    text event holds at most one line.
+
+    With verify_coverage=True, RuntimeError is raised as soon as the event spans
+    leave a gap or overlap, or stop short of the end of the input. This is meant for
+    fuzzing. Input the spec drops without any event, such as "</>" or a tag cut off
+    by EOF, is reported as a gap too.
     """
     html_str: str
     if isinstance(html, (bytes, bytearray, memoryview)):
         html_str, _ = decode_html(bytes(html), transport_encoding=encoding)
     else:
         html_str = html
+    return Stream(
+        html_str,
+        spans=spans,
+        tokenizer_opts=tokenizer_opts,
+        stop_at_tags=stop_at_tags,
+        split_lines=split_lines,
+        verify_coverage=verify_coverage,
+    )
+
+
+def token_at_offset(events: Sequence[SpannedStreamEvent], offset: int) -> int | None:
+    """
+    Return the index of the event whose span covers offset.
+
+    events must come from stream(..., spans=True). Returns None when offset falls
+    outside every span, e.g. inside a dropped "</>" or past the end of the input.
+    """
+    starts = [span[0] for _, _, span in events]
+    index = bisect_right(starts, offset) - 1
+    if index < 0 or offset >= events[index][2][1]:
+        return None
+    return index
+
+
+def events_with_source(html: str | bytes | bytearray | memoryview) -> list[tuple[str, Any, str]]:
+    """
+    Return (event_type, data, source) for each event, where source is the text the event came from.
+
+    Sources are taken from the input after decoding, BOM removal and newline normalization,
+    so concatenating them gives back that input. Only markup the spec drops without an event,
+    such as "</>" or a tag cut off by EOF, is missing; it is never part of a neighbouring text's source.
+    """
+    events = stream(html, spans=True)
+    buffer = events._tokenizer.buffer
+    return [(event, data, buffer[start:end]) for event, data, (start, end) in events]
+
+
+def safe_split_points(html: str, approx_chunks: int) -> list[int]:
+    """
+    Return offsets where the input can be split into roughly approx_chunks parts.
+
+    Each offset follows a tag, comment or doctype and leaves the tokenizer in the
+    data state with no pending text, so stream(text[offset:]) yields the same events
+    as stream(text) from that offset on. Offsets index the input after BOM removal
+    and newline normalization, like spans. Input with no markup has no split points.
+    """
+    points: list[int] = []
+    if approx_chunks < 2:
+        return points
     sink = StreamSink()
     tokenizer = Tokenizer(sink)
-    tokenizer.initialize(html_str)
+    tokenizer.initialize(html)
+    buffer = tokenizer.buffer
+    length = tokenizer.length
+    chunk_size = length / approx_chunks
+    target = chunk_size
+    while not tokenizer.step():
+        sink.tokens.clear()
+        pos = tokenizer.pos
+        if pos < target or pos >= length or tokenizer.state != Tokenizer.DATA or tokenizer.text_buffer:
+            continue
+        # A U+FEFF here would be dropped as a BOM when restarting
+        if buffer[pos] != "\ufeff":
+            points.append(pos)
+            target = pos + chunk_size
+    return points
 
-    while True:
-        # Run one step of the tokenizer
-        is_eof = tokenizer.step()
 
-        # Yield any tokens produced by this step
-        if sink.tokens:
-            # Coalesce text tokens
-            text_buffer: list[str] = []
-            for event, data in sink.tokens:
-                if event == "text":
-                    text_buffer.append(data)
-                else:
-                    if text_buffer:
-                        yield ("text", "".join(text_buffer))
-                        text_buffer = []
-                    yield (event, data)
+def text_content(html: str | bytes | bytearray | memoryview, *, collapse_whitespace: bool = False) -> str:
+    """
+    Return all text in html in one tokenizer pass, without building a tree.
 
-            if text_buffer:
-                yield ("text", "".join(text_buffer))
+    Character references are decoded and script and style contents are skipped.
+    With collapse_whitespace=True, whitespace runs become a single space and
+    leading and trailing whitespace is removed.
+    """
+    parts: list[str] = []
+    skipping: str | None = None
+    for event, data in stream(html):
+        if event == "text":
+            if skipping is None:
+                parts.append(data)
+        elif event == "start":
+            if data[0] in _NON_TEXT_ELEMENTS:
+                skipping = data[0]
+        elif event == "end" and data == skipping:
+            skipping = None
+    text = "".join(parts)
+    if collapse_whitespace:
+        text = _WHITESPACE_RUN_PATTERN.sub(" ", text).strip(" ")
+    return text
 
-            sink.tokens.clear()
 
-        if is_eof:
-            break
+def tag_balance(
+    html: str | bytes | bytearray | memoryview, void_elements: Iterable[str] | None = None
+) -> dict[str, int]:
+    """
+    Return the number of start tags minus end tags for each tag name, without building a tree.
+
+    Names appear in the order first seen; balanced names map to 0. Void elements such as
+    <br> are ignored, and self-closing start tags like <div/> count as balanced. This is a
+    quick lint signal only: the tree builder closes and reopens elements in ways it ignores.
+
+    void_elements replaces the HTML void element names, for example with an empty set for
+    XML-like markup where every start tag needs an end tag.
+    """
+    html_str = decode_html(bytes(html))[0] if isinstance(html, (bytes, bytearray, memoryview)) else html
+    voids = VOID_ELEMENTS if void_elements is None else frozenset(name.lower() for name in void_elements)
+    sink = _TagBalanceSink(voids)
+    Tokenizer(sink).run(html_str)
+    return sink.balance
+
+
+def tokenizer_errors(html: str | bytes | bytearray | memoryview) -> list[tuple[int, str]]:
+    """
+    Return (offset, code) for each tokenizer parse error in html, without keeping any tokens.
+
+    Offsets index the input after decoding, BOM removal and newline normalization, like
+    spans. Tree construction errors, such as misnested tags, need a full parse with
+    JustHTML(html, collect_errors=True) and are not included.
+    """
+    html_str = decode_html(bytes(html))[0] if isinstance(html, (bytes, bytearray, memoryview)) else html
+    tokenizer = Tokenizer(_DiscardSink(), collect_errors=True)
+    tokenizer.run(html_str)
+    line_starts = [0] + [pos + 1 for pos in tokenizer._newline_positions or ()]
+    return [(line_starts[(error.line or 1) - 1] + (error.column or 1) - 1, error.code) for error in tokenizer.errors]
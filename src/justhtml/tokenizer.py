@@ -5,15 +5,27 @@ from bisect import bisect_right
 from typing import TYPE_CHECKING, Any
 
 if TYPE_CHECKING:
-    from collections.abc import Callable
+    from collections.abc import Callable, Iterable
 
+from .constants import HTML_INTEGRATION_POINT_SET, MATHML_TEXT_INTEGRATION_POINT_SET
 from .entities import decode_entities_in_text
 from .errors import generate_error_message
-from .tokens import AnyToken, CharacterTokens, CommentToken, Doctype, DoctypeToken, EOFToken, ParseError, Tag
+from .tokens import (
+    _ASCII_LOWER_TABLE,
+    AnyToken,
+    CharacterTokens,
+    CommentToken,
+    Doctype,
+    DoctypeToken,
+    EOFToken,
+    ParseError,
+    Tag,
+)
 
 _ATTR_VALUE_UNQUOTED_TERMINATORS = "\t\n\f >&\"'<=`\0"
-_ASCII_LOWER_TABLE = str.maketrans({chr(code): chr(code + 32) for code in range(65, 91)})
 _RCDATA_ELEMENTS = {"title", "textarea"}
+# Elements whose first newline is dropped by the tree builder (§13.2.6.4.7)
+_LEADING_NEWLINE_ELEMENTS = {"listing", "pre", "textarea"}
 _RAWTEXT_SWITCH_TAGS = {
     "script",
     "style",
@@ -24,11 +36,35 @@ _RAWTEXT_SWITCH_TAGS = {
     "textarea",
     "title",
 }
+# Script type strings that select a classic or module script (JavaScript MIME type essences, §8.1.5.1)
+_JAVASCRIPT_SCRIPT_TYPES = frozenset(
+    (
+        "application/ecmascript",
+        "application/javascript",
+        "application/x-ecmascript",
+        "application/x-javascript",
+        "module",
+        "text/ecmascript",
+        "text/javascript",
+        "text/javascript1.0",
+        "text/javascript1.1",
+        "text/javascript1.2",
+        "text/javascript1.3",
+        "text/javascript1.4",
+        "text/javascript1.5",
+        "text/jscript",
+        "text/livescript",
+        "text/x-ecmascript",
+        "text/x-javascript",
+    )
+)
 
 _ATTR_VALUE_DOUBLE_PATTERN = re.compile(r'["&\0]')
 _ATTR_VALUE_SINGLE_PATTERN = re.compile(r"['&\0]")
 _ATTR_VALUE_UNQUOTED_PATTERN = re.compile(f"[{re.escape(_ATTR_VALUE_UNQUOTED_TERMINATORS)}]")
 
+# '<' followed by a character that leaves the data state for markup (§13.2.5.6)
+_MARKUP_START_PATTERN = re.compile(r"<[A-Za-z!/?]")
 _TAG_NAME_RUN_PATTERN = re.compile(r"[^\t\n\f />\0]+")
 _ATTR_NAME_RUN_PATTERN = re.compile(r"[^\t\n\f />=\0\"'<]+")
 _COMMENT_RUN_PATTERN = re.compile(r"[^-\0]+")
@@ -42,6 +78,57 @@ for _plane in range(17):
     _xml_invalid_single_chars.append(chr(_base + 0xFFFF))
 
 _XML_COERCION_PATTERN = re.compile(r"[\f\uFDD0-\uFDEF" + "".join(_xml_invalid_single_chars) + "]")
+# Controls other than ASCII whitespace, and noncharacters; _INVALID_CHAR_PATTERN adds U+0000
+_INVALID_NON_NULL_CHAR_PATTERN = re.compile(
+    r"[\x01-\x08\x0b\x0e-\x1f\x7f-\x9f\uFDD0-\uFDEF" + "".join(_xml_invalid_single_chars) + "]"
+)
+_INVALID_CHAR_PATTERN = re.compile(
+    r"[\x00-\x08\x0b\x0e-\x1f\x7f-\x9f\uFDD0-\uFDEF" + "".join(_xml_invalid_single_chars) + "]"
+)
+
+
+def _apply_invalid_char_handler(handler: Callable[[str], str | None], pattern: re.Pattern[str], data: str) -> str:
+    """Replace each match of pattern in data with handler's result, dropping it on None."""
+    return pattern.sub(lambda match: handler(match.group(0)) or "", data)
+
+
+def _is_javascript_script(attrs: dict[str, str | None]) -> bool:
+    """Whether a script start tag's type (or legacy language) attribute selects JavaScript."""
+    script_type = attrs.get("type")
+    if script_type is None:
+        language = attrs.get("language")
+        if not language:
+            return True
+        script_type = "text/" + language
+    script_type = script_type.strip(" \t\n\f\r").translate(_ASCII_LOWER_TABLE)
+    return not script_type or script_type in _JAVASCRIPT_SCRIPT_TYPES
+
+
+def _takes_html_start_tags(node: Any) -> bool:
+    """Whether the tree builder inserts start tags below node as HTML elements.
+
+    True for HTML elements and for the SVG and MathML integration points (§13.2.6.5), where
+    <textarea>, <script> and the like still switch the content model.
+    """
+    namespace = node.namespace
+    if namespace is None or namespace == "html":
+        return True
+    key = (namespace, node.name)
+    if key == ("math", "annotation-xml"):
+        encoding = node.attrs.get("encoding")
+        if encoding is None:
+            return False
+        return encoding.translate(_ASCII_LOWER_TABLE) in {"text/html", "application/xhtml+xml"}
+    return key in HTML_INTEGRATION_POINT_SET or key in MATHML_TEXT_INTEGRATION_POINT_SET
+
+
+def has_markup(html: str) -> bool:
+    """Return True if html contains anything the tokenizer would treat as markup.
+
+    This is a cheap scan for '<' followed by an ASCII letter, '!', '/' or '?', without
+    tokenizing. Text such as "a < b" is not markup.
+    """
+    return _MARKUP_START_PATTERN.search(html) is not None
 
 
 def _is_noncharacter_codepoint(codepoint: int) -> bool:
@@ -79,12 +166,42 @@ def _coerce_comment_for_xml(text: str) -> str:
 
 
 class TokenizerOpts:
-    __slots__ = ("discard_bom", "exact_errors", "initial_rawtext_tag", "initial_state", "xml_coercion")
+    __slots__ = (
+        "capture_raw_attrs",
+        "collapse_whitespace",
+        "discard_bom",
+        "exact_errors",
+        "initial_rawtext_tag",
+        "initial_state",
+        "invalid_char_handler",
+        "invalid_char_replacement",
+        "max_attributes",
+        "max_comment_length",
+        "plain_non_js_scripts",
+        "raw_passthrough_tags",
+        "require_semicolon",
+        "split_on_newline",
+        "strip_leading_newline",
+        "xml_cdata",
+        "xml_coercion",
+    )
 
+    capture_raw_attrs: bool
+    collapse_whitespace: bool
     discard_bom: bool
     exact_errors: bool
     initial_rawtext_tag: str | None
     initial_state: int | None
+    invalid_char_handler: Callable[[str], str | None] | None
+    invalid_char_replacement: str | None
+    max_attributes: int | None
+    max_comment_length: int | None
+    plain_non_js_scripts: bool
+    raw_passthrough_tags: frozenset[str]
+    require_semicolon: bool
+    split_on_newline: bool
+    strip_leading_newline: bool
+    xml_cdata: bool
     xml_coercion: bool
 
     def __init__(
@@ -94,12 +211,52 @@ class TokenizerOpts:
         initial_state: int | None = None,
         initial_rawtext_tag: str | None = None,
         xml_coercion: bool = False,
+        invalid_char_replacement: str | None = "\ufffd",
+        capture_raw_attrs: bool = False,
+        collapse_whitespace: bool = False,
+        plain_non_js_scripts: bool = False,
+        max_attributes: int | None = None,
+        invalid_char_handler: Callable[[str], str | None] | None = None,
+        xml_cdata: bool = False,
+        max_comment_length: int | None = None,
+        require_semicolon: bool = False,
+        raw_passthrough_tags: Iterable[str] = (),
+        split_on_newline: bool = False,
+        strip_leading_newline: bool = False,
     ) -> None:
         self.exact_errors = bool(exact_errors)
         self.discard_bom = bool(discard_bom)
         self.initial_state = initial_state
         self.initial_rawtext_tag = initial_rawtext_tag
         self.xml_coercion = bool(xml_coercion)
+        # Substituted wherever the spec replaces U+0000 with U+FFFD; None drops the character.
+        self.invalid_char_replacement = invalid_char_replacement
+        # Keep the source text between the tag name and '>' on Tag.raw_attrs
+        self.capture_raw_attrs = bool(capture_raw_attrs)
+        # Collapse whitespace runs to one space in DATA and RCDATA text, after entity decoding
+        self.collapse_whitespace = bool(collapse_whitespace)
+        # Tokenize <script type="text/html"> and other non-JavaScript scripts as plain RAWTEXT,
+        # without the <!-- escaping rules. Browsers always apply them, so this is opt-in.
+        self.plain_non_js_scripts = bool(plain_non_js_scripts)
+        # Drop attributes past this many per tag, bounding memory on pathological input
+        self.max_attributes = max_attributes
+        # Called with each U+0000, control character or noncharacter in the source text, attribute
+        # values and comments, and with each U+0000 in tag and attribute names and DOCTYPEs; returns
+        # the replacement, or None to drop it. Characters from character references are not passed.
+        # Takes precedence over invalid_char_replacement.
+        self.invalid_char_handler = invalid_char_handler
+        # Tokenize <![CDATA[...]]> as text everywhere, as in XML, instead of only in SVG and MathML
+        self.xml_cdata = bool(xml_cdata)
+        # Truncate comment data to this many characters; the comment still ends at its real -->
+        self.max_comment_length = max_comment_length
+        # Only decode character references that end in ';'; stricter than browsers, for validation
+        self.require_semicolon = bool(require_semicolon)
+        # Extra element names whose content is RAWTEXT up to the matching end tag, e.g. template components
+        self.raw_passthrough_tags = frozenset(name.translate(_ASCII_LOWER_TABLE) for name in raw_passthrough_tags)
+        # Emit PLAINTEXT content as one Character token per line instead of one for the rest of the input
+        self.split_on_newline = bool(split_on_newline)
+        # Drop one newline right after <pre>, <listing> and <textarea>, as the tree builder does
+        self.strip_leading_newline = bool(strip_leading_newline)
 
 
 class Tokenizer:
@@ -167,6 +324,7 @@ class Tokenizer:
 
     __slots__ = (
         "_comment_token",
+        "_leading_newline_pos",
         "_newline_positions",
         "_state_handlers",
         "_tag_token",
@@ -177,6 +335,7 @@ class Tokenizer:
         "current_attr_value_has_amp",
         "current_char",
         "current_comment",
+        "current_comment_length",
         "current_doctype_force_quirks",
         "current_doctype_name",
         "current_doctype_public",
@@ -197,16 +356,20 @@ class Tokenizer:
         "pos",
         "rawtext_tag_name",
         "reconsume",
+        "replacement_char",
+        "script_escapes",
         "sink",
         "state",
         "temp_buffer",
         "text_buffer",
+        "text_end_pos",
         "text_start_pos",
         "track_node_locations",
         "track_tag_positions",
     )
 
     _comment_token: CommentToken
+    _leading_newline_pos: int
     _newline_positions: list[int] | None
     _state_handlers: list[Callable[[Tokenizer], bool]]
     _tag_token: Tag
@@ -219,6 +382,8 @@ class Tokenizer:
     current_attr_value_has_amp: bool
     current_char: str | None
     current_comment: list[str]
+    # Characters buffered in current_comment, only counted while max_comment_length is set
+    current_comment_length: int
     current_doctype_force_quirks: bool
     current_doctype_name: list[str]
     current_doctype_public: list[str] | None
@@ -239,10 +404,15 @@ class Tokenizer:
     pos: int
     rawtext_tag_name: str | None
     reconsume: bool
+    replacement_char: str
+    # False while tokenizing a non-JavaScript script under TokenizerOpts.plain_non_js_scripts
+    script_escapes: bool
     sink: Any
     state: int
     temp_buffer: list[str]
     text_buffer: list[str]
+    # Where the last text run, token or dropped markup ended; the next text run starts here
+    text_end_pos: int
     text_start_pos: int
 
     # _STATE_HANDLERS is defined at the end of the file
@@ -258,6 +428,8 @@ class Tokenizer:
     ) -> None:
         self.sink = sink
         self.opts = opts or TokenizerOpts()
+        replacement = self.opts.invalid_char_replacement
+        self.replacement_char = "" if replacement is None else replacement
         self.collect_errors = collect_errors
         self.track_node_locations = bool(track_node_locations)
         self.track_tag_positions = bool(track_tag_positions)
@@ -276,6 +448,7 @@ class Tokenizer:
 
         # Reusable buffers to avoid per-token allocations.
         self.text_buffer = []
+        self.text_end_pos = 0
         self.text_start_pos = 0
         self.current_tag_name = []
         self.current_tag_attrs = {}
@@ -285,16 +458,20 @@ class Tokenizer:
         self.current_tag_self_closing = False
         self.current_tag_kind = Tag.START
         self.current_comment = []
+        self.current_comment_length = 0
         self.current_doctype_name = []
         self.current_doctype_public = None  # None = not set, [] = empty string
         self.current_doctype_system = None  # None = not set, [] = empty string
         self.current_doctype_force_quirks = False
         self.last_start_tag_name = None
         self.rawtext_tag_name = None
+        self.script_escapes = True
         self.original_tag_name = []
         self.temp_buffer = []
         self._tag_token = Tag(Tag.START, "", {}, False)
         self._comment_token = CommentToken("")
+        # Offset just past the last <pre>, <listing> or <textarea> start tag, for strip_leading_newline
+        self._leading_newline_pos = -1
 
     def initialize(self, html: str | None) -> None:
         if html and html[0] == "\ufeff" and self.opts.discard_bom:
@@ -316,6 +493,7 @@ class Tokenizer:
         self.last_token_start_pos = None
         self.errors = []
         self.text_buffer.clear()
+        self.text_end_pos = 0
         self.text_start_pos = 0
         self.current_tag_name.clear()
         self.current_tag_attrs = {}
@@ -323,6 +501,7 @@ class Tokenizer:
         self.current_attr_value.clear()
         self.current_attr_value_has_amp = False
         self.current_comment.clear()
+        self.current_comment_length = 0
         self.current_doctype_name.clear()
         self.current_doctype_public = None
         self.current_doctype_system = None
@@ -330,8 +509,10 @@ class Tokenizer:
         self.current_tag_self_closing = False
         self.current_tag_kind = Tag.START
         self.rawtext_tag_name = self.opts.initial_rawtext_tag
+        self.script_escapes = True
         self.temp_buffer.clear()
         self.last_start_tag_name = None
+        self._leading_newline_pos = -1
         self._tag_token.kind = Tag.START
         self._tag_token.name = ""
         self._tag_token.attrs = {}
@@ -398,6 +579,69 @@ class Tokenizer:
         handler = self._STATE_HANDLERS[self.state]  # type: ignore[attr-defined]
         return handler(self)  # type: ignore[no-any-return]
 
+    def content_model(self) -> str:
+        """Return the content model of the current state.
+
+        One of "DATA", "RCDATA", "RAWTEXT" or "PLAINTEXT". Script data, including its
+        escaped states, reports "RAWTEXT"; tag, comment and doctype states report "DATA".
+        """
+        state = self.state
+        if self.RCDATA <= state <= self.RCDATA_END_TAG_NAME:
+            return "RCDATA"
+        if self.RAWTEXT <= state <= self.RAWTEXT_END_TAG_NAME or state >= self.SCRIPT_DATA_ESCAPED:
+            return "RAWTEXT"
+        if state == self.PLAINTEXT:
+            return "PLAINTEXT"
+        return "DATA"
+
+    def in_text_mode(self) -> bool:
+        """Return True while consuming RCDATA, RAWTEXT, script data or PLAINTEXT."""
+        return self.content_model() != "DATA"
+
+    def unclosed_rawtext(self) -> str | None:
+        """Return the element whose RCDATA, RAWTEXT or script data ran to the end of the input.
+
+        After tokenizing "<script>alert(1)" this is "script"; it is None when the input
+        ended in DATA or PLAINTEXT, so truncated documents can be told from complete ones.
+        """
+        if self.pos < self.length or self.content_model() not in ("RCDATA", "RAWTEXT"):
+            return None
+        return self.rawtext_tag_name
+
+    def set_content_model(self, name: str, tag_name: str | None = None) -> None:
+        """Switch to the "DATA", "RCDATA", "RAWTEXT" or "PLAINTEXT" content model.
+
+        For RCDATA and RAWTEXT, tag_name is the element whose end tag leaves the
+        content model again; pass "script" with RAWTEXT for script data. Raises
+        ValueError for any other name.
+        """
+        state = _CONTENT_MODEL_STATES.get(name)
+        if state is None:
+            raise ValueError(f"Unknown content model: {name!r}")
+        self.state = state
+        self.rawtext_tag_name = tag_name
+
+    def reset_to_data(self) -> None:
+        """Switch to the DATA state, discarding text buffered since the last token.
+
+        Unlike set_content_model("DATA"), nothing consumed so far is emitted: pending
+        RCDATA, RAWTEXT, script or PLAINTEXT text and a partly read end tag are dropped.
+        Tokenizing resumes at the current position.
+        """
+        if self.reconsume:
+            # The character to reconsume is read again from the buffer
+            self.reconsume = False
+            if self.current_char is not None:
+                self.pos -= 1
+        self.state = self.DATA
+        self.rawtext_tag_name = None
+        self.script_escapes = True
+        self.text_buffer.clear()
+        self.text_end_pos = self.pos
+        self.temp_buffer.clear()
+        self.current_tag_name.clear()
+        self.original_tag_name.clear()
+
     def run(self, html: str | None) -> None:
         self.initialize(html)
         handlers = self._STATE_HANDLERS  # type: ignore[attr-defined]
@@ -409,12 +653,12 @@ class Tokenizer:
     # Helper methods
     # ---------------------
 
-    def _peek_char(self, offset: int) -> str | None:
-        """Peek ahead at character at current position + offset without consuming"""
-        peek_pos = self.pos + offset
-        if peek_pos < self.length:
-            return self.buffer[peek_pos]
-        return None
+    def _null_replacement(self) -> str:
+        # Called once per U+0000 the spec replaces, so invalid_char_handler sees every occurrence
+        handler = self.opts.invalid_char_handler
+        if handler is None:
+            return self.replacement_char
+        return handler("\0") or ""
 
     def _append_text_chunk(self, chunk: str) -> None:
         self._append_text(chunk)
@@ -563,6 +807,8 @@ class Tokenizer:
             return True
         if c == ">":
             self._emit_error("empty-end-tag")
+            # "</>" is dropped without a token, so text after it starts past it
+            self.text_end_pos = self.pos
             self.state = self.DATA
             return False
 
@@ -573,7 +819,6 @@ class Tokenizer:
         return False
 
     def _state_tag_name(self) -> bool:
-        replacement = "\ufffd"
         append_tag_char = self.current_tag_name.append
         buffer = self.buffer
         length = self.length
@@ -648,7 +893,7 @@ class Tokenizer:
             # c == "\0" - the only remaining possibility after fast-path
             self.pos = pos
             self._emit_error("unexpected-null-character")
-            append_tag_char(replacement)
+            append_tag_char(self._null_replacement())
 
     def _state_before_attribute_name(self) -> bool:
         buffer = self.buffer
@@ -707,7 +952,7 @@ class Tokenizer:
             self.current_attr_value_has_amp = False
             if c == "\0":
                 self._emit_error("unexpected-null-character")
-                c = "\ufffd"
+                c = self._null_replacement()
             elif "A" <= c <= "Z":
                 c = chr(ord(c) + 32)
 
@@ -716,7 +961,6 @@ class Tokenizer:
             return False  # Let main loop dispatch to avoid recursion
 
     def _state_attribute_name(self) -> bool:
-        replacement = "\ufffd"
         append_attr_char = self.current_attr_name.append
         buffer = self.buffer
         length = self.length
@@ -748,7 +992,7 @@ class Tokenizer:
                         if next_char in (" ", "\t", "\n", "\f"):
                             pos += 1
                             self.pos = pos
-                            self._finish_attribute()
+                            # The attribute is finished in AFTER_ATTRIBUTE_NAME: an '=' may still follow
                             self.state = self.AFTER_ATTRIBUTE_NAME
                             return False  # Let main loop dispatch to avoid recursion
                         if next_char == ">":
@@ -779,7 +1023,6 @@ class Tokenizer:
                 self._emit_token(EOFToken())
                 return True
             if c in ("\t", "\n", "\f", " "):
-                self._finish_attribute()
                 self.state = self.AFTER_ATTRIBUTE_NAME
                 return False  # Let main loop dispatch to avoid recursion
             if c == "/":
@@ -796,7 +1039,7 @@ class Tokenizer:
                 return False
             if c == "\0":
                 self._emit_error("unexpected-null-character")
-                append_attr_char(replacement)
+                append_attr_char(self._null_replacement())
                 continue
             self._emit_error("unexpected-character-in-attribute-name")
             append_attr_char(c)
@@ -847,7 +1090,7 @@ class Tokenizer:
             self.current_attr_value_has_amp = False
             if c == "\0":
                 self._emit_error("unexpected-null-character")
-                c = "\ufffd"
+                c = self._null_replacement()
             elif "A" <= c <= "Z":
                 c = chr(ord(c) + 32)
             self.current_attr_name.append(c)
@@ -888,7 +1131,6 @@ class Tokenizer:
             return self._state_attribute_value_unquoted()
 
     def _state_attribute_value_double(self) -> bool:
-        replacement = "\ufffd"
         stop_pattern = _ATTR_VALUE_DOUBLE_PATTERN
         buffer = self.buffer
         length = self.length
@@ -940,10 +1182,9 @@ class Tokenizer:
             else:
                 # c == "\0" - the only remaining possibility after fast-path
                 self._emit_error("unexpected-null-character")
-                self._append_attr_value_char(replacement)
+                self._append_attr_value_char(self._null_replacement())
 
     def _state_attribute_value_single(self) -> bool:
-        replacement = "\ufffd"
         stop_pattern = _ATTR_VALUE_SINGLE_PATTERN
         buffer = self.buffer
         length = self.length
@@ -995,10 +1236,9 @@ class Tokenizer:
             else:
                 # c == "\0" - the only remaining possibility after fast-path
                 self._emit_error("unexpected-null-character")
-                self._append_attr_value_char(replacement)
+                self._append_attr_value_char(self._null_replacement())
 
     def _state_attribute_value_unquoted(self) -> bool:
-        replacement = "\ufffd"
         stop_pattern = _ATTR_VALUE_UNQUOTED_PATTERN
         buffer = self.buffer
         length = self.length
@@ -1050,7 +1290,7 @@ class Tokenizer:
                 self._emit_error("unexpected-character-in-unquoted-attribute-value")
             if c == "\0":
                 self._emit_error("unexpected-null-character")
-                self._append_attr_value_char(replacement)
+                self._append_attr_value_char(self._null_replacement())
                 continue
             self._append_attr_value_char(c)
 
@@ -1098,8 +1338,9 @@ class Tokenizer:
             return True
         if c == ">":
             self.current_tag_self_closing = True
-            self._emit_current_tag()
-            self.state = self.DATA
+            # A self-closing <style/> or <title/> still switches the content model
+            if not self._emit_current_tag():
+                self.state = self.DATA
             return False
         self._emit_error("unexpected-character-after-solidus-in-tag")
         self._reconsume_current()
@@ -1117,7 +1358,10 @@ class Tokenizer:
             self.state = self.DOCTYPE
             return False
         if self._consume_if("[CDATA["):
-            # CDATA sections are only valid in foreign content (SVG/MathML)
+            # CDATA sections are only valid in foreign content (SVG/MathML), unless opts.xml_cdata is set
+            if self.opts.xml_cdata:
+                self.state = self.CDATA_SECTION
+                return False
             # Check if the adjusted current node is in a foreign namespace
             stack = self.sink.open_elements
             if stack:
@@ -1130,8 +1374,7 @@ class Tokenizer:
             self._emit_error("cdata-in-html-content")
             self.current_comment.clear()
             # Add the consumed "[CDATA[" text to the comment
-            for ch in "[CDATA[":
-                self.current_comment.append(ch)
+            self._append_comment("[CDATA[")
             self.state = self.BOGUS_COMMENT
             return False
         self._emit_error("incorrectly-opened-comment")
@@ -1141,7 +1384,6 @@ class Tokenizer:
         return False
 
     def _state_comment_start(self) -> bool:
-        replacement = "\ufffd"
         c = self._get_char()
         if c is None:
             self._emit_error("eof-in-comment")
@@ -1158,14 +1400,13 @@ class Tokenizer:
             return False
         if c == "\0":
             self._emit_error("unexpected-null-character")
-            self.current_comment.append(replacement)
+            self._append_comment(self._null_replacement())
         else:
-            self.current_comment.append(c)
+            self._append_comment(c)
         self.state = self.COMMENT
         return False
 
     def _state_comment_start_dash(self) -> bool:
-        replacement = "\ufffd"
         c = self._get_char()
         if c is None:
             self._emit_error("eof-in-comment")
@@ -1182,14 +1423,13 @@ class Tokenizer:
             return False
         if c == "\0":
             self._emit_error("unexpected-null-character")
-            self.current_comment.extend(("-", replacement))
+            self._append_comment("-" + self._null_replacement())
         else:
-            self.current_comment.extend(("-", c))
+            self._append_comment("-" + c)
         self.state = self.COMMENT
         return False
 
     def _state_comment(self) -> bool:
-        replacement = "\ufffd"
         while True:
             if self._consume_comment_run():
                 continue
@@ -1211,10 +1451,9 @@ class Tokenizer:
                 return False
             # c == "\0" - the only remaining possibility after _consume_comment_run
             self._emit_error("unexpected-null-character")
-            self.current_comment.append(replacement)
+            self._append_comment(self._null_replacement())
 
     def _state_comment_end_dash(self) -> bool:
-        replacement = "\ufffd"
         c = self._get_char()
         if c is None:
             self._emit_error("eof-in-comment")
@@ -1226,16 +1465,15 @@ class Tokenizer:
             return False
         if c == "\0":
             self._emit_error("unexpected-null-character")
-            self.current_comment.extend(("-", replacement))
+            self._append_comment("-" + self._null_replacement())
             self.state = self.COMMENT
             return False
         # Per spec: append "-" and current char, switch to COMMENT state
-        self.current_comment.extend(("-", c))
+        self._append_comment("-" + c)
         self.state = self.COMMENT
         return False
 
     def _state_comment_end(self) -> bool:
-        replacement = "\ufffd"
         c = self._get_char()
         if c is None:
             self._emit_error("eof-in-comment")
@@ -1250,20 +1488,19 @@ class Tokenizer:
             self.state = self.COMMENT_END_BANG
             return False
         if c == "-":
-            self.current_comment.append("-")
+            self._append_comment("-")
             return False
         if c == "\0":
             self._emit_error("unexpected-null-character")
-            self.current_comment.extend(("--", replacement))
+            self._append_comment("--" + self._null_replacement())
             self.state = self.COMMENT
             return False
         self._emit_error("incorrectly-closed-comment")
-        self.current_comment.extend(("--", c))
+        self._append_comment("--" + c)
         self.state = self.COMMENT
         return False
 
     def _state_comment_end_bang(self) -> bool:
-        replacement = "\ufffd"
         c = self._get_char()
         if c is None:
             self._emit_error("eof-in-comment")
@@ -1271,9 +1508,7 @@ class Tokenizer:
             self._emit_token(EOFToken())
             return True
         if c == "-":
-            self.current_comment.append("-")
-            self.current_comment.append("-")
-            self.current_comment.append("!")
+            self._append_comment("--!")
             self.state = self.COMMENT_END_DASH
             return False
         if c == ">":
@@ -1283,21 +1518,14 @@ class Tokenizer:
             return False
         if c == "\0":
             self._emit_error("unexpected-null-character")
-            self.current_comment.append("-")
-            self.current_comment.append("-")
-            self.current_comment.append("!")
-            self.current_comment.append(replacement)
+            self._append_comment("--!" + self._null_replacement())
             self.state = self.COMMENT
             return False
-        self.current_comment.append("-")
-        self.current_comment.append("-")
-        self.current_comment.append("!")
-        self.current_comment.append(c)
+        self._append_comment("--!" + c)
         self.state = self.COMMENT
         return False
 
     def _state_bogus_comment(self) -> bool:
-        replacement = "\ufffd"
         while True:
             c = self._get_char()
             if c is None:
@@ -1309,9 +1537,9 @@ class Tokenizer:
                 self.state = self.DATA
                 return False
             if c == "\0":
-                self.current_comment.append(replacement)
+                self._append_comment(self._null_replacement())
             else:
-                self.current_comment.append(c)
+                self._append_comment(c)
 
     def _state_doctype(self) -> bool:
         c = self._get_char()
@@ -1356,7 +1584,7 @@ class Tokenizer:
                 self.current_doctype_name.append(chr(ord(c) + 32))
             elif c == "\0":
                 self._emit_error("unexpected-null-character")
-                self.current_doctype_name.append("\ufffd")
+                self.current_doctype_name.append(self._null_replacement())
             else:
                 self.current_doctype_name.append(c)
             self.state = self.DOCTYPE_NAME
@@ -1383,18 +1611,19 @@ class Tokenizer:
                 continue
             if c == "\0":
                 self._emit_error("unexpected-null-character")
-                self.current_doctype_name.append("\ufffd")
+                self.current_doctype_name.append(self._null_replacement())
                 continue
             self.current_doctype_name.append(c)
 
     def _state_after_doctype_name(self) -> bool:
-        if self._consume_case_insensitive("PUBLIC"):
-            self.state = self.AFTER_DOCTYPE_PUBLIC_KEYWORD
-            return False
-        if self._consume_case_insensitive("SYSTEM"):
-            self.state = self.AFTER_DOCTYPE_SYSTEM_KEYWORD
-            return False
         while True:
+            # Keywords may follow any amount of whitespace after the name
+            if self._consume_case_insensitive("PUBLIC"):
+                self.state = self.AFTER_DOCTYPE_PUBLIC_KEYWORD
+                return False
+            if self._consume_case_insensitive("SYSTEM"):
+                self.state = self.AFTER_DOCTYPE_SYSTEM_KEYWORD
+                return False
             c = self._get_char()
             if c is None:
                 self._emit_error("eof-in-doctype")
@@ -1529,7 +1758,7 @@ class Tokenizer:
                 return False
             if c == "\0":
                 self._emit_error("unexpected-null-character")
-                self.current_doctype_public.append("\ufffd")
+                self.current_doctype_public.append(self._null_replacement())
                 continue
             if c == ">":
                 self._emit_error("abrupt-doctype-public-identifier")
@@ -1555,7 +1784,7 @@ class Tokenizer:
                 return False
             if c == "\0":
                 self._emit_error("unexpected-null-character")
-                self.current_doctype_public.append("\ufffd")
+                self.current_doctype_public.append(self._null_replacement())
                 continue
             if c == ">":
                 self._emit_error("abrupt-doctype-public-identifier")
@@ -1673,7 +1902,7 @@ class Tokenizer:
                 return False
             if c == "\0":
                 self._emit_error("unexpected-null-character")
-                self.current_doctype_system.append("\ufffd")
+                self.current_doctype_system.append(self._null_replacement())
                 continue
             if c == ">":
                 self._emit_error("abrupt-doctype-system-identifier")
@@ -1699,7 +1928,7 @@ class Tokenizer:
                 return False
             if c == "\0":
                 self._emit_error("unexpected-null-character")
-                self.current_doctype_system.append("\ufffd")
+                self.current_doctype_system.append(self._null_replacement())
                 continue
             if c == ">":
                 self._emit_error("abrupt-doctype-system-identifier")
@@ -1758,8 +1987,6 @@ class Tokenizer:
         c = self.buffer[pos]
         self.pos = pos + 1
         self.current_char = c
-        if c == "<":
-            self.current_token_start_pos = pos
         if self.collect_errors and not c.isascii() and _is_noncharacter_codepoint(ord(c)):
             self._emit_error_at_pos("noncharacter-in-input-stream", pos)
         return c
@@ -1767,11 +1994,23 @@ class Tokenizer:
     def _reconsume_current(self) -> None:
         self.reconsume = True
 
+    def _append_comment(self, text: str) -> None:
+        max_comment_length = self.opts.max_comment_length
+        if max_comment_length is not None:
+            # Buffer one character past the limit so _emit_comment can tell the comment was cut
+            room = max_comment_length + 1 - self.current_comment_length
+            if room <= 0:
+                return
+            text = text[:room]
+            self.current_comment_length += len(text)
+        self.current_comment.append(text)
+
     def _append_text(self, text: str) -> None:
         """Append text to buffer, recording start position if this is the first chunk."""
         if not self.text_buffer:
-            # Record where text started (current position before this chunk)
-            self.text_start_pos = self.pos
+            # A run starts where the previous text, token or dropped markup ended, not at self.pos:
+            # text such as the "<" of "<3" is appended after the tokenizer has read past it.
+            self.text_start_pos = self.text_end_pos
         self.text_buffer.append(text)
 
     def _flush_text(self) -> None:
@@ -1787,6 +2026,11 @@ class Tokenizer:
 
         # Calculate raw text length before any processing for position tracking
         raw_len = len(data)
+        # The run ends where the markup that interrupted it starts, or where reading stopped
+        if self.current_char is not None and self.current_token_start_pos > self.text_start_pos:
+            self.text_end_pos = self.current_token_start_pos
+        else:
+            self.text_end_pos = self.pos
 
         self.text_buffer.clear()
         # U+0000 NULL is a parse error in text.
@@ -1822,6 +2066,10 @@ class Tokenizer:
 
                 search_from = idx + 1
 
+        handler = self.opts.invalid_char_handler
+        if handler is not None:
+            # Before decoding, so characters from character references are left alone
+            data = _apply_invalid_char_handler(handler, _INVALID_CHAR_PATTERN, data)
         # Per HTML5 spec:
         # - RCDATA state (title, textarea): decode character references
         # - RAWTEXT state (style, script, etc): do NOT decode
@@ -1834,7 +2082,15 @@ class Tokenizer:
         else:
             if "&" in data:
                 report_error = self._emit_error if self.collect_errors else None
-                data = decode_entities_in_text(data, report_error=report_error)
+                data = decode_entities_in_text(
+                    data, report_error=report_error, require_semicolon=self.opts.require_semicolon
+                )
+            if self.text_start_pos == self._leading_newline_pos and data.startswith("\n"):
+                data = data[1:]
+                if not data:
+                    return
+            if self.opts.collapse_whitespace:
+                data = _WHITESPACE_PATTERN.sub(" ", data)
         # Apply XML coercion if enabled
         if self.opts.xml_coercion:
             data = _coerce_text_for_xml(data)
@@ -1867,15 +2123,26 @@ class Tokenizer:
             attr_value_buffer.clear()
             self.current_attr_value_has_amp = False
             return
+        max_attributes = self.opts.max_attributes
+        if max_attributes is not None and len(attrs) >= max_attributes:
+            self._emit_error("too-many-attributes")
+            attr_value_buffer.clear()
+            self.current_attr_value_has_amp = False
+            return
         if not attr_value_buffer:
             value = ""
         elif len(attr_value_buffer) == 1:
             value = attr_value_buffer[0]
         else:
             value = "".join(attr_value_buffer)
+        handler = self.opts.invalid_char_handler
+        if handler is not None:
+            value = _apply_invalid_char_handler(handler, _INVALID_NON_NULL_CHAR_PATTERN, value)
         if self.current_attr_value_has_amp:
             report_error = self._emit_error if self.collect_errors else None
-            value = decode_entities_in_text(value, in_attribute=True, report_error=report_error)
+            value = decode_entities_in_text(
+                value, in_attribute=True, report_error=report_error, require_semicolon=self.opts.require_semicolon
+            )
         attrs[name] = value
         attr_value_buffer.clear()
         self.current_attr_value_has_amp = False
@@ -1896,38 +2163,57 @@ class Tokenizer:
         tag.name = name
         tag.attrs = attrs
         tag.self_closing = self.current_tag_self_closing
+        tag.acknowledged_self_closing = False
         if self.track_tag_positions:
             tag.start_pos = self.current_token_start_pos
             tag.end_pos = self.pos
         else:
             tag.start_pos = None
             tag.end_pos = None
+        tag.raw_attrs = self._raw_attrs() if self.opts.capture_raw_attrs else None
         self.last_token_start_pos = tag.start_pos
 
         switched_to_rawtext = False
-        if self.current_tag_kind == Tag.START:
+        if self.current_tag_kind == Tag.END:
+            # End tags keep neither attributes nor the self-closing flag (§13.2.5.1 end tag token)
+            if attrs:
+                self._emit_error("end-tag-with-attributes")
+                tag.attrs = {}
+            if tag.self_closing:
+                self._emit_error("end-tag-with-trailing-solidus")
+                tag.self_closing = False
+        elif self.current_tag_kind == Tag.START:
             self.last_start_tag_name = name
-            needs_rawtext_check = name in _RAWTEXT_SWITCH_TAGS or name == "plaintext"
+            raw_passthrough = name in self.opts.raw_passthrough_tags
+            needs_rawtext_check = name in _RAWTEXT_SWITCH_TAGS or name == "plaintext" or raw_passthrough
             if needs_rawtext_check:
                 stack = self.sink.open_elements
-                current_node = stack[-1] if stack else None
-                namespace = current_node.namespace if current_node else None
-                if namespace is None or namespace == "html":
+                if not stack or _takes_html_start_tags(stack[-1]):
                     if name in _RCDATA_ELEMENTS:
                         self.state = self.RCDATA
                         self.rawtext_tag_name = name
                         switched_to_rawtext = True
-                    elif name in _RAWTEXT_SWITCH_TAGS:
+                    elif name in _RAWTEXT_SWITCH_TAGS or raw_passthrough:
                         self.state = self.RAWTEXT
                         self.rawtext_tag_name = name
+                        if name == "script":
+                            self.script_escapes = not self.opts.plain_non_js_scripts or _is_javascript_script(attrs)
                         switched_to_rawtext = True
                     else:
                         # Must be "plaintext" - the only other way needs_rawtext_check can be True
                         self.state = self.PLAINTEXT
                         switched_to_rawtext = True
+            # An SVG <textarea> is not an HTML textarea, so its newline is kept
+            if (
+                self.opts.strip_leading_newline
+                and name in _LEADING_NEWLINE_ELEMENTS
+                and (name != "textarea" or switched_to_rawtext)
+            ):
+                self._leading_newline_pos = self.pos
         # Remember current state before emitting
 
         # Emit token to sink
+        self.text_end_pos = self.pos
         if self.collect_errors:
             self._record_token_position()
         result = self.sink.process_token(tag)
@@ -1942,9 +2228,40 @@ class Tokenizer:
         self.current_tag_kind = Tag.START
         return switched_to_rawtext
 
+    def _emit_appropriate_end_tag(self, name: str) -> None:
+        # Appropriate end tags in RCDATA/RAWTEXT/script data bypass the tag states, so the
+        # shared tag token is not involved; positions still span from '<' to '>'.
+        attrs: dict[str, str | None] = {}
+        tag = Tag(Tag.END, name, attrs, False)
+        if self.track_tag_positions:
+            tag.start_pos = self.current_token_start_pos
+            tag.end_pos = self.pos
+        if self.opts.capture_raw_attrs:
+            tag.raw_attrs = ""
+        self._flush_text()
+        self._emit_token(tag)
+
+    def _raw_attrs(self) -> str:
+        # The tag name ends at the same characters that leave the tag name state (§13.2.5.8),
+        # and the tag is emitted right after consuming '>'.
+        buffer = self.buffer
+        end = self.pos - 1
+        pos = self.current_token_start_pos + (2 if self.current_tag_kind == Tag.END else 1)
+        while pos < end and buffer[pos] not in " \t\n\f/>":
+            pos += 1
+        return buffer[pos:end]
+
     def _emit_comment(self) -> None:
         data = "".join(self.current_comment)
         self.current_comment.clear()
+        self.current_comment_length = 0
+        max_comment_length = self.opts.max_comment_length
+        if max_comment_length is not None and len(data) > max_comment_length:
+            self._emit_error("comment-too-long")
+            data = data[:max_comment_length]
+        handler = self.opts.invalid_char_handler
+        if handler is not None:
+            data = _apply_invalid_char_handler(handler, _INVALID_NON_NULL_CHAR_PATTERN, data)
         # Apply XML coercion if enabled
         if self.opts.xml_coercion:
             data = _coerce_comment_for_xml(data)
@@ -1971,6 +2288,7 @@ class Tokenizer:
         self._emit_token(DoctypeToken(doctype))
 
     def _emit_token(self, token: AnyToken) -> None:
+        self.text_end_pos = self.pos
         if self.collect_errors:
             self._record_token_position()
         self.sink.process_token(token)
@@ -2039,13 +2357,10 @@ class Tokenizer:
         )
 
     def _consume_if(self, literal: str) -> bool:
-        end = self.pos + len(literal)
-        if end > self.length:
-            return False
-        segment = self.buffer[self.pos : end]
-        if segment != literal:
+        # startswith() with a start index compares in place instead of slicing
+        if not self.buffer.startswith(literal, self.pos):
             return False
-        self.pos = end
+        self.pos += len(literal)
         return True
 
     def _consume_case_insensitive(self, literal: str) -> bool:
@@ -2053,7 +2368,8 @@ class Tokenizer:
         if end > self.length:
             return False
         segment = self.buffer[self.pos : end]
-        if segment.lower() != literal.lower():
+        # ASCII case-insensitive match; literals are ASCII so only A-Z need folding
+        if segment.translate(_ASCII_LOWER_TABLE) != literal.lower():
             return False
         self.pos = end
         return True
@@ -2067,8 +2383,16 @@ class Tokenizer:
 
         match = _COMMENT_RUN_PATTERN.match(self.buffer, pos)
         if match:
-            chunk = match.group(0)
-            self.current_comment.append(chunk)
+            end = match.end()
+            max_comment_length = self.opts.max_comment_length
+            if max_comment_length is None:
+                self.current_comment.append(self.buffer[pos:end])
+            else:
+                # Don't copy more of a huge run than can be kept; _emit_comment truncates the rest
+                end = min(end, pos + max(max_comment_length + 1 - self.current_comment_length, 0))
+                if end > pos:
+                    self.current_comment.append(self.buffer[pos:end])
+                    self.current_comment_length += end - pos
             self.pos = match.end()
             return True
         return False
@@ -2110,6 +2434,8 @@ class Tokenizer:
         if c == ">":
             # End of CDATA section
             self._flush_text()
+            # "]]>" itself produces no text, so text after it starts past it
+            self.text_end_pos = self.pos
             self.state = self.DATA
             return False
         # Not the end - we saw ']]' but not '>'. Emit one ']' and check if the next char is another ']'
@@ -2167,6 +2493,7 @@ class Tokenizer:
 
             # Handle EOF
             if pos >= length:
+                self.current_char = None
                 self._flush_text()
                 self._emit_token(EOFToken())
                 return True
@@ -2174,7 +2501,7 @@ class Tokenizer:
             # Handle special characters - we're at one of them after find()
             if null_index == pos:
                 self._emit_error("unexpected-null-character")
-                self._append_text("\ufffd")
+                self._append_text(self._null_replacement())
                 pos += 1
                 self.pos = pos
             elif amp_index == pos:
@@ -2185,6 +2512,7 @@ class Tokenizer:
             else:
                 # lt_index == pos - the only remaining possibility
                 # Less-than sign - might be start of end tag
+                self.current_token_start_pos = pos
                 pos += 1
                 self.pos = pos
                 self.state = self.RCDATA_LESS_THAN_SIGN
@@ -2208,7 +2536,7 @@ class Tokenizer:
             self.original_tag_name.append(c)
             self.state = self.RCDATA_END_TAG_NAME
             return False
-        self.text_buffer.extend(("<", "/"))
+        self._append_text("</")
         self._reconsume_current()
         self.state = self.RCDATA
         return False
@@ -2225,16 +2553,14 @@ class Tokenizer:
             tag_name = "".join(self.current_tag_name)
             if tag_name == self.rawtext_tag_name:
                 if c == ">":
-                    attrs: dict[str, str | None] = {}
-                    tag = Tag(Tag.END, tag_name, attrs, False)
-                    self._flush_text()
-                    self._emit_token(tag)
+                    self._emit_appropriate_end_tag(tag_name)
                     self.state = self.DATA
                     self.rawtext_tag_name = None
                     self.original_tag_name.clear()
                     return False
                 if c in (" ", "\t", "\n", "\r", "\f"):
                     # Whitespace after tag name - switch to BEFORE_ATTRIBUTE_NAME
+                    self._flush_text()
                     self.current_tag_kind = Tag.END
                     self.current_tag_attrs = {}
                     self.state = self.BEFORE_ATTRIBUTE_NAME
@@ -2248,7 +2574,7 @@ class Tokenizer:
             # If we hit EOF or tag doesn't match, emit as text
             if c is None:
                 # EOF - emit incomplete tag as text (preserve original case) then EOF
-                self.text_buffer.extend(("<", "/"))
+                self._append_text("</")
                 for ch in self.original_tag_name:
                     self._append_text(ch)
                 self.current_tag_name.clear()
@@ -2257,7 +2583,7 @@ class Tokenizer:
                 self._emit_token(EOFToken())
                 return True
             # Not a matching end tag - emit as text (preserve original case)
-            self.text_buffer.extend(("<", "/"))
+            self._append_text("</")
             for ch in self.original_tag_name:
                 self._append_text(ch)
             self.current_tag_name.clear()
@@ -2289,7 +2615,7 @@ class Tokenizer:
                     chunk = buffer[pos:null_index]
                     self._append_text_chunk(chunk)
                 self._emit_error("unexpected-null-character")
-                self._append_text("\ufffd")
+                self._append_text(self._null_replacement())
                 pos = null_index + 1
                 self.pos = pos
                 continue
@@ -2298,26 +2624,23 @@ class Tokenizer:
                     chunk = buffer[pos:length]
                     self._append_text_chunk(chunk)
                 self.pos = length
+                self.current_char = None
                 self._flush_text()
                 self._emit_token(EOFToken())
                 return True
             if lt_index > pos:
                 chunk = buffer[pos:lt_index]
                 self._append_text_chunk(chunk)
+            self.current_token_start_pos = lt_index
             pos = lt_index + 1
             self.pos = pos
             # Handle script escaped transition before treating '<' as markup boundary
-            if self.rawtext_tag_name == "script":
-                next1 = self._peek_char(0)
-                next2 = self._peek_char(1)
-                next3 = self._peek_char(2)
-                if next1 == "!" and next2 == "-" and next3 == "-":
-                    self.text_buffer.extend(["<", "!", "-", "-"])
-                    self._get_char()
-                    self._get_char()
-                    self._get_char()
-                    self.state = self.SCRIPT_DATA_ESCAPED
-                    return False
+            if self.rawtext_tag_name == "script" and self.script_escapes and buffer.startswith("!--", pos):
+                self._append_text("<!--")
+                self.pos = pos + 3
+                self.current_char = "-"
+                self.state = self.SCRIPT_DATA_ESCAPED
+                return False
             self.state = self.RAWTEXT_LESS_THAN_SIGN
             return False
 
@@ -2339,7 +2662,7 @@ class Tokenizer:
             self.original_tag_name.append(c)
             self.state = self.RAWTEXT_END_TAG_NAME
             return False
-        self.text_buffer.extend(("<", "/"))
+        self._append_text("</")
         self._reconsume_current()
         self.state = self.RAWTEXT
         return False
@@ -2352,20 +2675,28 @@ class Tokenizer:
                 self.current_tag_name.append(c.lower())
                 self.original_tag_name.append(c)
                 continue
+            # Raw passthrough tags such as "my-widget" may also contain digits and '-'
+            rawtext_tag_name = self.rawtext_tag_name
+            if (
+                c is not None
+                and rawtext_tag_name is not None
+                and rawtext_tag_name.startswith("".join(self.current_tag_name) + c)
+            ):
+                self.current_tag_name.append(c)
+                self.original_tag_name.append(c)
+                continue
             # End of tag name - check if it matches
             tag_name = "".join(self.current_tag_name)
             if tag_name == self.rawtext_tag_name:
                 if c == ">":
-                    attrs: dict[str, str | None] = {}
-                    tag = Tag(Tag.END, tag_name, attrs, False)
-                    self._flush_text()
-                    self._emit_token(tag)
+                    self._emit_appropriate_end_tag(tag_name)
                     self.state = self.DATA
                     self.rawtext_tag_name = None
                     self.original_tag_name.clear()
                     return False
                 if c in (" ", "\t", "\n", "\r", "\f"):
                     # Whitespace after tag name - switch to BEFORE_ATTRIBUTE_NAME
+                    self._flush_text()
                     self.current_tag_kind = Tag.END
                     self.current_tag_attrs = {}
                     self.state = self.BEFORE_ATTRIBUTE_NAME
@@ -2379,7 +2710,7 @@ class Tokenizer:
             # If we hit EOF or tag doesn't match, emit as text
             if c is None:
                 # EOF - emit incomplete tag as text (preserve original case) then EOF
-                self.text_buffer.extend(("<", "/"))
+                self._append_text("</")
                 for ch in self.original_tag_name:
                     self._append_text(ch)
                 self.current_tag_name.clear()
@@ -2388,7 +2719,7 @@ class Tokenizer:
                 self._emit_token(EOFToken())
                 return True
             # Not a matching end tag - emit as text (preserve original case)
-            self.text_buffer.extend(("<", "/"))
+            self._append_text("</")
             for ch in self.original_tag_name:
                 self._append_text(ch)
             self.current_tag_name.clear()
@@ -2399,14 +2730,23 @@ class Tokenizer:
 
     def _state_plaintext(self) -> bool:
         # PLAINTEXT state - consume everything as text, no end tag
-        if self.pos < self.length:
-            remaining = self.buffer[self.pos :]
+        buffer = self.buffer
+        length = self.length
+        while self.pos < length:
+            end = length
+            if self.opts.split_on_newline:
+                newline = buffer.find("\n", self.pos)
+                if newline != -1:
+                    end = newline + 1
+            remaining = buffer[self.pos : end]
             # Replace null bytes with replacement character
             if "\0" in remaining:
-                remaining = remaining.replace("\0", "\ufffd")
+                parts = remaining.split("\0")
+                remaining = parts[0] + "".join(self._null_replacement() + part for part in parts[1:])
                 self._emit_error("unexpected-null-character")
             self._append_text(remaining)
-            self.pos = self.length
+            self.pos = end
+            self._flush_text()
         self._flush_text()
         self._emit_token(EOFToken())
         return True
@@ -2422,11 +2762,12 @@ class Tokenizer:
             self.state = self.SCRIPT_DATA_ESCAPED_DASH
             return False
         if c == "<":
+            self.current_token_start_pos = self.pos - 1
             self.state = self.SCRIPT_DATA_ESCAPED_LESS_THAN_SIGN
             return False
         if c == "\0":
             self._emit_error("unexpected-null-character")
-            self._append_text("\ufffd")
+            self._append_text(self._null_replacement())
             return False
         self._append_text(c)
         return False
@@ -2442,11 +2783,12 @@ class Tokenizer:
             self.state = self.SCRIPT_DATA_ESCAPED_DASH_DASH
             return False
         if c == "<":
+            self.current_token_start_pos = self.pos - 1
             self.state = self.SCRIPT_DATA_ESCAPED_LESS_THAN_SIGN
             return False
         if c == "\0":
             self._emit_error("unexpected-null-character")
-            self._append_text("\ufffd")
+            self._append_text(self._null_replacement())
             self.state = self.SCRIPT_DATA_ESCAPED
             return False
         self._append_text(c)
@@ -2464,6 +2806,7 @@ class Tokenizer:
             return False
         if c == "<":
             self._append_text("<")
+            self.current_token_start_pos = self.pos - 1
             self.state = self.SCRIPT_DATA_ESCAPED_LESS_THAN_SIGN
             return False
         if c == ">":
@@ -2472,7 +2815,7 @@ class Tokenizer:
             return False
         if c == "\0":
             self._emit_error("unexpected-null-character")
-            self._append_text("\ufffd")
+            self._append_text(self._null_replacement())
             self.state = self.SCRIPT_DATA_ESCAPED
             return False
         self._append_text(c)
@@ -2505,7 +2848,7 @@ class Tokenizer:
             self._reconsume_current()
             self.state = self.SCRIPT_DATA_ESCAPED_END_TAG_NAME
             return False
-        self.text_buffer.extend(("<", "/"))
+        self._append_text("</")
         self._reconsume_current()
         self.state = self.SCRIPT_DATA_ESCAPED
         return False
@@ -2523,6 +2866,7 @@ class Tokenizer:
 
         if is_appropriate:
             if c in (" ", "\t", "\n", "\r", "\f"):
+                self._flush_text()
                 self.current_tag_kind = Tag.END
                 self.current_tag_attrs = {}
                 self.state = self.BEFORE_ATTRIBUTE_NAME
@@ -2534,17 +2878,14 @@ class Tokenizer:
                 self.state = self.SELF_CLOSING_START_TAG
                 return False
             if c == ">":
-                self._flush_text()
-                attrs: dict[str, str | None] = {}
-                tag = Tag(Tag.END, tag_name, attrs, False)
-                self._emit_token(tag)
+                self._emit_appropriate_end_tag(tag_name)
                 self.state = self.DATA
                 self.rawtext_tag_name = None
                 self.current_tag_name.clear()
                 self.original_tag_name.clear()
                 return False
         # Not an appropriate end tag
-        self.text_buffer.extend(("<", "/"))
+        self._append_text("</")
         for ch in self.temp_buffer:
             self._append_text(ch)
         self._reconsume_current()
@@ -2586,7 +2927,7 @@ class Tokenizer:
             return False
         if c == "\0":
             self._emit_error("unexpected-null-character")
-            self._append_text("\ufffd")
+            self._append_text(self._null_replacement())
             return False
         self._append_text(c)
         return False
@@ -2607,7 +2948,7 @@ class Tokenizer:
             return False
         if c == "\0":
             self._emit_error("unexpected-null-character")
-            self._append_text("\ufffd")
+            self._append_text(self._null_replacement())
             self.state = self.SCRIPT_DATA_DOUBLE_ESCAPED
             return False
         self._append_text(c)
@@ -2635,7 +2976,7 @@ class Tokenizer:
             return False
         if c == "\0":
             self._emit_error("unexpected-null-character")
-            self._append_text("\ufffd")
+            self._append_text(self._null_replacement())
             self.state = self.SCRIPT_DATA_DOUBLE_ESCAPED
             return False
         self._append_text(c)
@@ -2742,3 +3083,11 @@ Tokenizer._STATE_HANDLERS = [  # type: ignore[attr-defined]
     Tokenizer._state_script_data_double_escaped_less_than_sign,
     Tokenizer._state_script_data_double_escape_end,
 ]
+
+# Content model names accepted by Tokenizer.set_content_model
+_CONTENT_MODEL_STATES = {
+    "DATA": Tokenizer.DATA,
+    "RCDATA": Tokenizer.RCDATA,
+    "RAWTEXT": Tokenizer.RAWTEXT,
+    "PLAINTEXT": Tokenizer.PLAINTEXT,
+}
@@ -1,10 +1,76 @@
 from __future__ import annotations
 
-from typing import Literal
+from typing import Any, Literal
+
+from .treebuilder_utils import doctype_error_and_quirks
+
+# Start tags that open foreign content from HTML (§13.2.6.5).
+_FOREIGN_ROOTS = frozenset(("math", "svg"))
+_DIR_KEYWORDS = frozenset(("auto", "ltr", "rtl"))
+_ASCII_WHITESPACE = " \t\n\f\r"
+# Enumerated attribute values are ASCII case-insensitive; str.lower() would also fold non-ASCII letters
+_ASCII_LOWER_TABLE = str.maketrans({chr(code): chr(code + 32) for code in range(65, 91)})
+_ASCII_DIGITS = "0123456789"
+
+
+def _skip(text: str, pos: int, chars: str) -> int:
+    length = len(text)
+    while pos < length and text[pos] in chars:
+        pos += 1
+    return pos
+
+
+def _parse_meta_refresh(content: str) -> tuple[int, str | None] | None:
+    """The shared declarative refresh steps (HTML §4.2.5.3); None when content is invalid."""
+    pos = _skip(content, 0, _ASCII_WHITESPACE)
+    digits_end = _skip(content, pos, _ASCII_DIGITS)
+    if digits_end == pos and not content.startswith(".", pos):
+        return None
+    delay = int(content[pos:digits_end]) if digits_end > pos else 0
+    # Fractional seconds are consumed and ignored
+    pos = _skip(content, digits_end, _ASCII_DIGITS + ".")
+    length = len(content)
+    if pos == length:
+        return delay, None
+    if content[pos] not in ";," + _ASCII_WHITESPACE:
+        return None
+    pos = _skip(content, pos, _ASCII_WHITESPACE)
+    if pos < length and content[pos] in ";,":
+        pos = _skip(content, pos + 1, _ASCII_WHITESPACE)
+    if pos == length:
+        return delay, None
+    # An optional "url =" prefix, then an optional quote that also ends the URL
+    if content[pos : pos + 3].translate(_ASCII_LOWER_TABLE) == "url":
+        pos = _skip(content, pos + 3, _ASCII_WHITESPACE)
+        if pos < length and content[pos] == "=":
+            pos = _skip(content, pos + 1, _ASCII_WHITESPACE)
+    url = content[pos:]
+    if url[:1] in ("'", '"'):
+        quote = url[0]
+        url = url[1:]
+        end = url.find(quote)
+        if end != -1:
+            url = url[:end]
+    return delay, url
+
+
+def _preview(text: str, max_len: int) -> str:
+    if len(text) <= max_len:
+        return repr(text)
+    return f"{text[:max_len]!r}... len={len(text)}"
 
 
 class Tag:
-    __slots__ = ("attrs", "end_pos", "kind", "name", "self_closing", "start_pos")
+    __slots__ = (
+        "acknowledged_self_closing",
+        "attrs",
+        "end_pos",
+        "kind",
+        "name",
+        "raw_attrs",
+        "self_closing",
+        "start_pos",
+    )
 
     START: Literal[0] = 0
     END: Literal[1] = 1
@@ -14,6 +80,11 @@ class Tag:
     attrs: dict[str, str | None]
     end_pos: int | None
     self_closing: bool
+    # Set by the tree builder when it acknowledges self_closing (§13.2.6); an
+    # unacknowledged flag on a start tag is a parse error.
+    acknowledged_self_closing: bool
+    # Source text between the tag name and '>', when TokenizerOpts.capture_raw_attrs is set
+    raw_attrs: str | None
     start_pos: int | None
 
     def __init__(
@@ -29,9 +100,107 @@ class Tag:
         self.name = name
         self.attrs = attrs if attrs is not None else {}
         self.self_closing = bool(self_closing)
+        self.acknowledged_self_closing = False
+        self.raw_attrs = None
         self.start_pos = start_pos
         self.end_pos = end_pos
 
+    @property
+    def is_start_tag(self) -> bool:
+        return self.kind == Tag.START
+
+    @property
+    def is_end_tag(self) -> bool:
+        return self.kind == Tag.END
+
+    @property
+    def is_foreign_root(self) -> bool:
+        """True for svg and math start tags, which switch the tree builder to foreign content."""
+        return self.kind == Tag.START and self.name in _FOREIGN_ROOTS
+
+    @property
+    def is_template(self) -> bool:
+        """True for template start and end tags; template contents still tokenize as normal markup."""
+        return self.name == "template"
+
+    @property
+    def lang(self) -> str | None:
+        """The lang attribute as written, or None when absent."""
+        return self.attrs.get("lang")
+
+    @property
+    def direction(self) -> str | None:
+        """The dir attribute lowercased to "ltr", "rtl" or "auto", or None when absent or invalid."""
+        value = self.attrs.get("dir")
+        if value is None:
+            return None
+        value = value.translate(_ASCII_LOWER_TABLE)
+        return value if value in _DIR_KEYWORDS else None
+
+    def meta_refresh(self) -> tuple[int, str | None] | None:
+        """(delay, url) of a <meta http-equiv="refresh"> start tag, or None.
+
+        The delay is in whole seconds; url is None when content has no URL part.
+        None is also returned when the content attribute is missing or malformed.
+        """
+        if self.kind != Tag.START or self.name != "meta":
+            return None
+        http_equiv = self.attrs.get("http-equiv")
+        content = self.attrs.get("content")
+        if http_equiv is None or http_equiv.translate(_ASCII_LOWER_TABLE) != "refresh" or content is None:
+            return None
+        return _parse_meta_refresh(content)
+
+    def attrs_equal(self, other: Tag) -> bool:
+        """True when both tags have the same attribute names and values, in any order."""
+        return self.attrs == other.attrs
+
+    def attrs_diff(
+        self, other: Tag
+    ) -> tuple[dict[str, str | None], dict[str, str | None], dict[str, tuple[str | None, str | None]]]:
+        """Return (added, removed, changed) attributes going from this tag to other.
+
+        changed maps each name to its (old, new) values.
+        """
+        attrs = self.attrs
+        other_attrs = other.attrs
+        added = {name: value for name, value in other_attrs.items() if name not in attrs}
+        removed = {name: value for name, value in attrs.items() if name not in other_attrs}
+        changed = {
+            name: (value, other_attrs[name])
+            for name, value in attrs.items()
+            if name in other_attrs and other_attrs[name] != value
+        }
+        return added, removed, changed
+
+    def serialize_attrs(self) -> str:
+        """Serialize the attributes as in a start tag: ' name="value"' each, boolean attributes minimized."""
+        from .serialize import serialize_attrs  # noqa: PLC0415
+
+        return serialize_attrs(self.attrs)
+
+    def to_dict(self) -> dict[str, Any]:
+        """JSON-serializable fields; attrs become [name, value] pairs in source order."""
+        return {
+            "type": "StartTag" if self.kind == Tag.START else "EndTag",
+            "name": self.name,
+            "attrs": [[name, value] for name, value in self.attrs.items()],
+            "self_closing": self.self_closing,
+            "acknowledged_self_closing": self.acknowledged_self_closing,
+            "raw_attrs": self.raw_attrs,
+            "start_pos": self.start_pos,
+            "end_pos": self.end_pos,
+        }
+
+    def debug_str(self, max_len: int = 20) -> str:
+        if self.kind == Tag.END:
+            return f"EndTag({self.name})"
+        count = len(self.attrs)
+        return f"StartTag({self.name}, {count} attr{'' if count == 1 else 's'}, self_closing={self.self_closing})"
+
+    def __repr__(self) -> str:
+        return self.debug_str()
+
 
 class CharacterTokens:
     __slots__ = ("data",)
@@ -41,6 +210,15 @@ class CharacterTokens:
     def __init__(self, data: str) -> None:
         self.data = data
 
+    def to_dict(self) -> dict[str, Any]:
+        return {"type": "Characters", "data": self.data}
+
+    def debug_str(self, max_len: int = 20) -> str:
+        return f"Characters({_preview(self.data, max_len)})"
+
+    def __repr__(self) -> str:
+        return self.debug_str()
+
 
 class CommentToken:
     __slots__ = ("data", "start_pos")
@@ -52,6 +230,15 @@ class CommentToken:
         self.data = data
         self.start_pos = start_pos
 
+    def to_dict(self) -> dict[str, Any]:
+        return {"type": "Comment", "data": self.data, "start_pos": self.start_pos}
+
+    def debug_str(self, max_len: int = 20) -> str:
+        return f"Comment({_preview(self.data, max_len)})"
+
+    def __repr__(self) -> str:
+        return self.debug_str()
+
 
 class Doctype:
     __slots__ = ("force_quirks", "name", "public_id", "system_id")
@@ -73,6 +260,10 @@ class Doctype:
         self.system_id = system_id
         self.force_quirks = bool(force_quirks)
 
+    def quirks_mode(self) -> str:
+        """Return "no-quirks", "quirks" or "limited-quirks", the document mode this DOCTYPE selects."""
+        return doctype_error_and_quirks(self)[1]
+
 
 class DoctypeToken:
     __slots__ = ("doctype",)
@@ -82,14 +273,73 @@ class DoctypeToken:
     def __init__(self, doctype: Doctype) -> None:
         self.doctype = doctype
 
+    def to_dict(self) -> dict[str, Any]:
+        doctype = self.doctype
+        return {
+            "type": "Doctype",
+            "name": doctype.name,
+            "public_id": doctype.public_id,
+            "system_id": doctype.system_id,
+            "force_quirks": doctype.force_quirks,
+        }
+
+    def debug_str(self, max_len: int = 20) -> str:
+        doctype = self.doctype
+        parts = [str(doctype.name)]
+        if doctype.public_id is not None:
+            parts.append(f"public={_preview(doctype.public_id, max_len)}")
+        if doctype.system_id is not None:
+            parts.append(f"system={_preview(doctype.system_id, max_len)}")
+        if doctype.force_quirks:
+            parts.append("force_quirks=True")
+        return f"Doctype({', '.join(parts)})"
+
+    def __repr__(self) -> str:
+        return self.debug_str()
+
 
 class EOFToken:
     __slots__ = ()
 
+    def to_dict(self) -> dict[str, Any]:
+        return {"type": "EOF"}
+
+    def debug_str(self, max_len: int = 20) -> str:
+        return "EOF"
+
+    def __repr__(self) -> str:
+        return self.debug_str()
+
 
 AnyToken = Tag | CharacterTokens | CommentToken | DoctypeToken | EOFToken
 
 
+def token_from_dict(data: dict[str, Any]) -> AnyToken:
+    """Rebuild a token from the output of its to_dict(). Raises ValueError for an unknown type."""
+    token_type = data["type"]
+    if token_type in ("StartTag", "EndTag"):
+        tag = Tag(
+            Tag.START if token_type == "StartTag" else Tag.END,
+            data["name"],
+            dict(data["attrs"]),
+            data["self_closing"],
+            data["start_pos"],
+            data["end_pos"],
+        )
+        tag.acknowledged_self_closing = data["acknowledged_self_closing"]
+        tag.raw_attrs = data["raw_attrs"]
+        return tag
+    if token_type == "Characters":
+        return CharacterTokens(data["data"])
+    if token_type == "Comment":
+        return CommentToken(data["data"], data["start_pos"])
+    if token_type == "Doctype":
+        return DoctypeToken(Doctype(data["name"], data["public_id"], data["system_id"], data["force_quirks"]))
+    if token_type == "EOF":
+        return EOFToken()
+    raise ValueError(f"Unknown token type: {token_type!r}")
+
+
 class TokenSinkResult:
     __slots__ = ()
 
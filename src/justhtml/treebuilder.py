@@ -427,11 +427,8 @@ class TreeBuilder(TreeBuilderModesMixin):
                         if self._is_html_integration_point(current) or self._is_mathml_text_integration_point(current):
                             pass
                         # Don't pop when inserting new svg/math elements
-                        if isinstance(current_token, Tag) and current_token.kind == Tag.START:
-                            # Optimization: Tokenizer already lowercases tag names
-                            name_lower = current_token.name
-                            if name_lower in {"svg", "math"}:
-                                pass
+                        if isinstance(current_token, Tag) and current_token.is_foreign_root:
+                            pass
 
                     # Special handling: text at integration points inserts directly, bypassing mode dispatch
                     if isinstance(current_token, CharacterTokens):
@@ -636,6 +633,9 @@ class TreeBuilder(TreeBuilderModesMixin):
             node._start_tag_start = tag.start_pos
             node._start_tag_end = tag.end_pos
         node._self_closing = bool(getattr(tag, "self_closing", False))
+        if not push and tag.self_closing:
+            # Void elements and self-closing foreign elements are never pushed; that is the acknowledgement.
+            tag.acknowledged_self_closing = True
 
         if self.tokenizer is not None and self.tokenizer.track_node_locations:
             node._origin_pos = tag.start_pos
@@ -1173,6 +1173,7 @@ class TreeBuilder(TreeBuilderModesMixin):
             new_tag = Tag(Tag.START, adjusted_name, attrs, token.self_closing)
             # For foreign elements, honor the self-closing flag
             self._insert_element(new_tag, push=not token.self_closing, namespace=namespace)
+            token.acknowledged_self_closing = new_tag.acknowledged_self_closing
             return None
 
         # Only START and END tag kinds exist, and START returns above
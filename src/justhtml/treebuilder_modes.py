@@ -536,6 +536,7 @@ class TreeBuilderModesMixin:
         attrs = self._prepare_foreign_attributes("math", token.attrs)
         new_tag = Tag(Tag.START, token.name, attrs, token.self_closing)
         self._insert_element(new_tag, push=not token.self_closing, namespace="math")
+        token.acknowledged_self_closing = new_tag.acknowledged_self_closing
         return
 
     def _handle_body_start_svg(self, token: Tag) -> None:
@@ -544,6 +545,7 @@ class TreeBuilderModesMixin:
         attrs = self._prepare_foreign_attributes("svg", token.attrs)
         new_tag = Tag(Tag.START, adjusted_name, attrs, token.self_closing)
         self._insert_element(new_tag, push=not token.self_closing, namespace="svg")
+        token.acknowledged_self_closing = new_tag.acknowledged_self_closing
         return
 
     def _handle_body_start_li(self, token: Tag) -> None:
@@ -902,6 +904,7 @@ class TreeBuilderModesMixin:
         img_token = Tag(Tag.START, "img", token.attrs, token.self_closing)
         self._reconstruct_active_formatting_elements()
         self._insert_element(img_token, push=False)
+        token.acknowledged_self_closing = img_token.acknowledged_self_closing
         self.frameset_ok = False
         return
 
@@ -1092,6 +1095,8 @@ class TreeBuilderModesMixin:
                         self._parse_error("unexpected-hidden-input-in-table")
                         self._insert_element(token, push=True)
                         self._pop_current()  # push=True always adds to stack
+                        # Popped straight away like a void element, so a trailing solidus is acknowledged.
+                        token.acknowledged_self_closing = token.self_closing
                         return None
                 if name == "form":
                     self._parse_error("unexpected-form-in-table")
@@ -1251,6 +1256,7 @@ class TreeBuilderModesMixin:
                 if name == "col":
                     self._insert_element(token, push=True)
                     self._pop_current()  # push=True always adds to stack
+                    token.acknowledged_self_closing = token.self_closing
                     return None
                 if name == "template":
                     # Template is handled by delegating to IN_HEAD
@@ -1553,7 +1559,7 @@ class TreeBuilderModesMixin:
                     return ("reprocess", self.mode, token)
                 if name in {"script", "template"}:
                     return self._mode_in_head(token)
-                if name in {"svg", "math"}:
+                if token.is_foreign_root:
                     # For foreign elements, honor the self-closing flag
                     self._reconstruct_active_formatting_elements()
                     self._insert_element(token, push=not token.self_closing, namespace=name)
@@ -1820,6 +1826,7 @@ class TreeBuilderModesMixin:
             if token.kind == Tag.START and token.name == "frame":
                 self._insert_element(token, push=True)
                 self._pop_current()
+                token.acknowledged_self_closing = token.self_closing
                 return None
             if token.kind == Tag.START and token.name == "noframes":
                 # Per spec: use IN_HEAD rules but preserve current mode for TEXT restoration
@@ -93,6 +93,18 @@ class TestEncoding(unittest.TestCase):
         self.assertEqual(text, "hi")
         self.assertEqual(name, "utf-8")
 
+    def test_decode_utf16_surrogates(self):
+        html = "<p>a\U0001f600b</p>"
+        for label, codec in (("utf-16le", "utf-16-le"), ("utf-16be", "utf-16-be")):
+            with self.subTest(label=label):
+                self.assertEqual(decode_html(html.encode(codec), transport_encoding=label), (html, label))
+
+        # An unpaired high surrogate becomes U+FFFD instead of failing or pairing with the next unit
+        data = "<p>".encode("utf-16-le") + b"\x3d\xd8" + "x</p>".encode("utf-16-le")
+        self.assertEqual(decode_html(data, transport_encoding="utf-16le")[0], "<p>\ufffdx</p>")
+        events = list(stream(data, encoding="utf-16le"))
+        self.assertEqual(events, [("start", ("p", {})), ("text", "\ufffdx"), ("end", "p")])
+
     def test_internal_helpers(self):
         self.assertIsNone(enc._strip_ascii_whitespace(None))
 
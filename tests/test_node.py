@@ -180,6 +180,13 @@ class TestNode(unittest.TestCase):
         assert comment.origin_offset is not None
         assert comment.origin_location == (1, comment.origin_offset + 1)
 
+    def test_node_origin_offset_for_comment_containing_lt(self):
+        # A '<' inside a comment or bogus comment must not move the token start.
+        doc = JustHTML("<p>a<!--<x--><?a<b>", track_node_locations=True, fragment=True)
+        p = doc.root.children[0]
+        comments = [child for child in p.children if child.name == "#comment"]
+        assert [comment.origin_offset for comment in comments] == [4, 13]
+
     def test_pre_ignores_single_leading_lf(self):
         # Start tag <pre> sets ignore_lf, and the very next leading LF is dropped.
         doc = JustHTML("<pre>\n</pre>")
@@ -14,6 +14,7 @@ from justhtml.serialize import (
     _is_layout_blocky_element,
     _normalize_formatting_whitespace,
     _should_pretty_indent_children,
+    serialize_attrs,
     serialize_end_tag,
     serialize_start_tag,
     to_html,
@@ -470,6 +471,12 @@ class TestSerialize(unittest.TestCase):
         output = doc.root.to_html(safe=False)
         assert "<div data-val></div>" in output
 
+    def test_serialize_attrs(self):
+        assert serialize_attrs(None) == ""
+        assert serialize_attrs({"id": "a", "hidden": None}) == ' id="a" hidden'
+        assert serialize_attrs({"hidden": None}, minimize_boolean_attributes=False) == ' hidden=""'
+        assert serialize_attrs({"title": "a b", "id": "c"}, quote_attr_values=False) == ' title="a b" id=c'
+
     def test_serialize_start_tag_quotes(self):
         # Prefer single quotes if the value contains a double quote but no single quote
         tag = serialize_start_tag("span", {"title": 'foo"bar'})
@@ -1,6 +1,18 @@
 import unittest
+from unittest import mock
 
 from justhtml import stream
+from justhtml.stream import (
+    StreamSink,
+    events_with_source,
+    safe_split_points,
+    tag_balance,
+    text_content,
+    token_at_offset,
+    tokenizer_errors,
+)
+from justhtml.tokenizer import TokenizerOpts
+from justhtml.tokens import CommentToken
 
 
 class TestStream(unittest.TestCase):
@@ -63,3 +75,429 @@ class TestStream(unittest.TestCase):
         events = list(stream(html))
         expected = [("end", "div")]
         assert events == expected
+
+    def test_unfinished_markup_at_eof_is_text(self):
+        cases = [
+            ("</", [("text", "</")]),
+            ("a</", [("text", "a</")]),
+            ("<div", [("text", "<div")]),
+            ("a</div", [("text", "a</div")]),
+            ("<p>x</p", [("start", ("p", {})), ("text", "x</p")]),
+        ]
+        for html, expected in cases:
+            with self.subTest(html=html):
+                assert list(stream(html)) == expected
+
+    def test_text_coalesced_across_tokenizer_steps(self):
+        # "<" that does not start a tag is re-emitted as text in a later step.
+        events = list(stream("a < b"))
+        assert events == [("text", "a < b")]
+
+    def test_spans(self):
+        html = '<!DOCTYPE html><p class="x">Hi<!--c--></p>tail'
+        events = list(stream(html, spans=True))
+        assert [(event, html[start:end]) for event, _, (start, end) in events] == [
+            ("doctype", "<!DOCTYPE html>"),
+            ("start", '<p class="x">'),
+            ("text", "Hi"),
+            ("comment", "<!--c-->"),
+            ("end", "</p>"),
+            ("text", "tail"),
+        ]
+        assert events[1][:2] == ("start", ("p", {"class": "x"}))
+
+    def test_spans_keep_raw_doctype(self):
+        # The structured fields are normalized; the span recovers the DOCTYPE exactly as written
+        html = '<!DOCTYPE   html   PUBLIC "  -//x "  >'
+        events = list(stream(html, spans=True))
+        assert events[0][:2] == ("doctype", ("html", "  -//x ", None))
+        start, end = events[0][2]
+        assert html[start:end] == html
+
+    def test_spans_rawtext_end_tags(self):
+        html = "<title>a</title ><script>x<!--<s>--></script>"
+        events = list(stream(html, spans=True))
+        assert [(event, html[start:end]) for event, _, (start, end) in events] == [
+            ("start", "<title>"),
+            ("text", "a"),
+            ("end", "</title >"),
+            ("start", "<script>"),
+            ("text", "x<!--<s>-->"),
+            ("end", "</script>"),
+        ]
+
+    def test_spans_index_normalized_input(self):
+        events = list(stream("\ufeffa\r\n<b>", spans=True))
+        assert events == [("text", "a\n", (0, 2)), ("start", ("b", {}), (2, 5))]
+
+    def test_spans_without_tokenizer(self):
+        sink = StreamSink()
+        sink.process_token(CommentToken("x"))
+        assert sink.tokens == [("comment", "x", -1, -1)]
+
+    def test_verify_coverage(self):
+        inputs = [
+            "",
+            "text only",
+            '<!DOCTYPE html><html lang=en><head><title>a &amp; b</title></head><body a="1" b=2 c>',
+            "<p>x<!-- c --><br/><svg><path/></svg>y</p>\r\n<script><!--<script></script>--></script>z",
+            "<textarea>\n</textarea><style>s</style><plaintext></p>",
+            "<div",
+            "a<3b</",
+            "<![CDATA[x]]><?pi?><!doctype>",
+        ]
+        for html in inputs:
+            with self.subTest(html=html):
+                events = list(stream(html, spans=True, verify_coverage=True))
+                assert [event[:2] for event in events] == list(stream(html))
+                lines = list(stream(html, split_lines=True, verify_coverage=True))
+                assert len(lines) >= len(events)
+        events = stream("<p>x</p><b>rest", stop_at_tags=["p"], verify_coverage=True)
+        assert list(events) == [("start", ("p", {}))]
+
+    def test_verify_coverage_reports_gaps(self):
+        with mock.patch.object(StreamSink, "process_characters", lambda self, data: None):
+            events = stream("<p>dropped</p>", verify_coverage=True)
+            assert next(events) == ("start", ("p", {}))
+            with self.assertRaisesRegex(RuntimeError, "'end' event starts at 10, expected 3"):
+                next(events)
+        with self.assertRaisesRegex(RuntimeError, "events end at 3 of 6"):
+            list(stream("<p></>", verify_coverage=True))
+
+    def test_verify_coverage_reports_markup_dropped_next_to_text(self):
+        # Text spans come from the tokenizer, so dropped markup is not absorbed by neighbouring text
+        assert list(stream("a</>b", spans=True)) == [("text", "a", (0, 1)), ("text", "b", (4, 5))]
+        with self.assertRaisesRegex(RuntimeError, "'text' event starts at 4, expected 1"):
+            list(stream("a</>b", verify_coverage=True))
+        assert list(stream("a<div class=x", spans=True)) == [("text", "a", (0, 1))]
+        with self.assertRaisesRegex(RuntimeError, "events end at 1 of 13"):
+            list(stream("a<div class=x", verify_coverage=True))
+
+    def test_token_at_offset(self):
+        html = "<p>ab</p></><i>"
+        events = list(stream(html, spans=True))
+        assert [event for event, _, _ in events] == ["start", "text", "end", "start"]
+        assert token_at_offset(events, 0) == 0
+        assert token_at_offset(events, 2) == 0
+        assert token_at_offset(events, 3) == 1
+        assert token_at_offset(events, 4) == 1
+        assert token_at_offset(events, 5) == 2
+        # "</>" is dropped, so its offsets belong to no event
+        assert token_at_offset(events, 10) is None
+        assert token_at_offset(events, 12) == 3
+        assert token_at_offset(events, len(html)) is None
+        assert token_at_offset(events, -1) is None
+        assert token_at_offset([], 0) is None
+
+    def test_newline_normalization(self):
+        # The whole input is normalized up front, so a CR at the end can never be split from its LF.
+        assert list(stream("a\r")) == [("text", "a\n")]
+        assert list(stream("a\r\nb")) == [("text", "a\nb")]
+        assert list(stream(b"a\r\nb")) == [("text", "a\nb")]
+
+    def test_attrs_keep_source_order(self):
+        events = list(stream("<p z=1 a=2 m=3 a=4>"))
+        assert list(events[0][1][1].items()) == [("z", "1"), ("a", "2"), ("m", "3")]
+
+    def test_attrs_are_snapshots(self):
+        events = stream('<a href="x"><a href="x">')
+        _, (_, first) = next(events)
+        first["href"] = "changed"
+        first["extra"] = "1"
+        _, (_, second) = next(events)
+        assert second == {"href": "x"}
+        assert second is not first
+
+    def test_invalid_char_replacement(self):
+        html = '<p a="x\0y"><!--c\0d--><textarea>t\0u</textarea><style>s\0t</style>'
+        cases = [("\ufffd", "\ufffd"), (" ", " "), (None, "")]
+        for replacement, expected in cases:
+            with self.subTest(replacement=replacement):
+                opts = TokenizerOpts(invalid_char_replacement=replacement)
+                events = list(stream(html, tokenizer_opts=opts))
+                assert events == [
+                    ("start", ("p", {"a": f"x{expected}y"})),
+                    ("comment", f"c{expected}d"),
+                    ("start", ("textarea", {})),
+                    ("text", f"t{expected}u"),
+                    ("end", "textarea"),
+                    ("start", ("style", {})),
+                    ("text", f"s{expected}t"),
+                    ("end", "style"),
+                ]
+
+    def test_invalid_char_replacement_default(self):
+        assert list(stream("<plaintext>a\0b")) == [("start", ("plaintext", {})), ("text", "a\ufffdb")]
+        opts = TokenizerOpts(invalid_char_replacement=None)
+        assert list(stream("<plaintext>a\0b", tokenizer_opts=opts)) == [("start", ("plaintext", {})), ("text", "ab")]
+
+    def test_invalid_char_replacement_keeps_decoded_replacement_characters(self):
+        # U+0000 is replaced before character references are decoded, so a decoded U+FFFD is kept
+        html = '<title>&#xFFFD;a\0b</title><p title="&#xFFFD;\0">&#xFFFD;x'
+        opts = TokenizerOpts(invalid_char_replacement=None)
+        assert list(stream(html, tokenizer_opts=opts)) == [
+            ("start", ("title", {})),
+            ("text", "\ufffdab"),
+            ("end", "title"),
+            ("start", ("p", {"title": "\ufffd"})),
+            ("text", "\ufffdx"),
+        ]
+        opts = TokenizerOpts(invalid_char_handler=lambda char: "?")
+        assert list(stream("&#xFFFD;\x01", tokenizer_opts=opts)) == [("text", "\ufffd?")]
+
+    def test_invalid_char_handler(self):
+        def handler(char):
+            return "[SOH]" if char == "\x01" else None
+
+        html = '<p a="x\0y">a\x01b\x7fc\ufdd0<!--\0--><textarea>\x01\0</textarea>'
+        opts = TokenizerOpts(invalid_char_replacement="?", invalid_char_handler=handler)
+        assert list(stream(html, tokenizer_opts=opts)) == [
+            ("start", ("p", {"a": "xy"})),
+            ("text", "a[SOH]bc"),
+            ("comment", ""),
+            ("start", ("textarea", {})),
+            ("text", "[SOH]"),
+            ("end", "textarea"),
+        ]
+        assert list(stream("a\x01\tb\n")) == [("text", "a\x01\tb\n")]
+
+    def test_invalid_char_handler_called_per_occurrence(self):
+        calls = []
+
+        def handler(char):
+            calls.append(char)
+            return f"<{ord(char)}>"
+
+        opts = TokenizerOpts(invalid_char_handler=handler)
+        assert list(stream("<p>hi</p>", tokenizer_opts=opts)) == [("start", ("p", {})), ("text", "hi"), ("end", "p")]
+        assert calls == []
+        html = '<p\0 a="x\0y\0\x01" b=&#1;>&#1;\x02<!--\x03\0--><style>\0\x04</style>'
+        assert list(stream(html, tokenizer_opts=opts)) == [
+            ("start", ("p<0>", {"a": "x<0>y<0><1>", "b": "\x01"})),
+            ("text", "\x01<2>"),
+            ("comment", "<3><0>"),
+            ("start", ("style", {})),
+            ("text", "<0><4>"),
+            ("end", "style"),
+        ]
+        # Characters from character references are never passed to the handler
+        assert calls == ["\0", "\0", "\0", "\x01", "\x02", "\0", "\x03", "\0", "\x04"]
+
+    def test_stop_at_tags(self):
+        html = "<html><head><title>T</title></head><body><p>x</p></body>"
+        events = stream(html, stop_at_tags=["body"])
+        assert list(events) == [
+            ("start", ("html", {})),
+            ("start", ("head", {})),
+            ("start", ("title", {})),
+            ("text", "T"),
+            ("end", "title"),
+            ("end", "head"),
+            ("start", ("body", {})),
+        ]
+        assert events.remaining() == "<p>x</p></body>"
+        assert list(events) == []
+
+    def test_stop_at_tags_ignores_end_tags(self):
+        events = stream("</body>a<body>b", stop_at_tags={"body"})
+        assert list(events) == [("end", "body"), ("text", "a"), ("start", ("body", {}))]
+        assert events.remaining() == "b"
+
+    def test_remaining(self):
+        events = stream("<p>a</p>b")
+        assert iter(events) is events
+        assert events.remaining() == "<p>a</p>b"
+        assert next(events) == ("start", ("p", {}))
+        assert events.remaining() == "a</p>b"
+        assert list(events) == [("text", "a"), ("end", "p"), ("text", "b")]
+        assert events.remaining() == ""
+
+    def test_last_event_type(self):
+        events = stream("<!DOCTYPE html><p>a<!--c--></p>")
+        assert events.last_event_type is None
+        seen = []
+        for event, _ in events:
+            seen.append(events.last_event_type)
+            assert events.last_event_type == event
+        assert seen == ["doctype", "start", "text", "comment", "end"]
+        # Exhausting the stream keeps the last event
+        assert events.last_event_type == "end"
+
+    def test_last_event_type_includes_pushed_events(self):
+        events = stream("<p>")
+        events.push("text", "x")
+        next(events)
+        assert events.last_event_type == "text"
+
+    def test_push(self):
+        events = stream("<p>a</p>")
+        assert next(events) == ("start", ("p", {}))
+        events.push("start", ("b", {}))
+        events.push("text", "x")
+        assert list(events) == [("text", "x"), ("start", ("b", {})), ("text", "a"), ("end", "p")]
+
+    def test_push_replays_event_with_span(self):
+        events = stream("<p>a</p>", spans=True)
+        first = next(events)
+        events.push(*first)
+        assert next(events) == ("start", ("p", {}), (0, 3))
+        assert events.remaining() == "a</p>"
+        assert next(events) == ("text", "a", (3, 4))
+
+    def test_push_default_span(self):
+        events = stream("", spans=True)
+        events.push("comment", "x")
+        assert list(events) == [("comment", "x", (-1, -1))]
+
+    def test_remaining_tells_whether_text_ended_at_eof(self):
+        cases = [("abc<b>", "<b>"), ("abc", ""), ("abc<", ""), ("a&amp;", "")]
+        for html, rest in cases:
+            with self.subTest(html=html):
+                events = stream(html)
+                assert next(events)[0] == "text"
+                assert events.remaining() == rest
+
+    def test_bom_is_stripped(self):
+        assert list(stream("\ufeffabc")) == [("text", "abc")]
+        assert list(stream("abc")) == [("text", "abc")]
+        assert list(stream(b"\xef\xbb\xbfabc")) == [("text", "abc")]
+        assert list(stream(b"abc")) == [("text", "abc")]
+        # A U+FEFF anywhere else is ordinary text
+        assert list(stream("a\ufeff")) == [("text", "a\ufeff")]
+
+    def test_bom_spans_are_post_strip_offsets(self):
+        for html in ("\ufeffa<b>", b"\xef\xbb\xbfa<b>"):
+            with self.subTest(html=html):
+                assert list(stream(html, spans=True)) == [("text", "a", (0, 1)), ("start", ("b", {}), (1, 4))]
+
+    def test_split_lines(self):
+        assert list(stream("a\nb\nc", split_lines=True)) == [("text", "a\n"), ("text", "b\n"), ("text", "c")]
+        assert list(stream("a\nb\nc")) == [("text", "a\nb\nc")]
+        assert list(stream("<pre>\n\nx\n</pre>", split_lines=True)) == [
+            ("start", ("pre", {})),
+            ("text", "\n"),
+            ("text", "\n"),
+            ("text", "x\n"),
+            ("end", "pre"),
+        ]
+
+    def test_split_lines_spans(self):
+        html = "a\n\nb<p>x\ny</p>"
+        events = list(stream(html, spans=True, split_lines=True))
+        assert [(data, html[start:end]) for _, data, (start, end) in events] == [
+            ("a\n", "a\n"),
+            ("\n", "\n"),
+            ("b", "b"),
+            (("p", {}), "<p>"),
+            ("x\n", "x\n"),
+            ("y", "y"),
+            ("p", "</p>"),
+        ]
+
+    def test_split_lines_spans_with_newline_reference(self):
+        # &#10; has no source newline to split at, so every line keeps the span of the whole run
+        events = list(stream("a&#10;b\nc", spans=True, split_lines=True))
+        assert events == [("text", "a\n", (0, 9)), ("text", "b\n", (0, 9)), ("text", "c", (0, 9))]
+
+    def test_spans_are_code_point_offsets(self):
+        html = "é😀<b>ü</b>"
+        events = list(stream(html, spans=True))
+        assert events == [
+            ("text", "é😀", (0, 2)),
+            ("start", ("b", {}), (2, 5)),
+            ("text", "ü", (5, 6)),
+            ("end", "b", (6, 10)),
+        ]
+
+    def test_safe_split_points(self):
+        html = (
+            "<!DOCTYPE html><title>a<b></title><p class=x>one<!--c--></p>\n"
+            "<style>p{}</style><script>if (a<b) f()</script><textarea>z</textarea>tail<b>x</b>"
+        ) * 3
+        events = list(stream(html, spans=True))
+        for chunks in (2, 4, 10, 100):
+            points = safe_split_points(html, chunks)
+            assert 0 < len(points) < chunks
+            for point in points:
+                with self.subTest(chunks=chunks, point=point):
+                    expected = [(event, data) for event, data, (start, _) in events if start >= point]
+                    assert list(stream(html[point:])) == expected
+
+    def test_safe_split_points_never_inside_raw_text(self):
+        html = "<style>" + "<p>" * 50 + "</style><p>"
+        assert safe_split_points(html, 10) == [len(html) - 3]
+
+    def test_safe_split_points_edge_cases(self):
+        assert safe_split_points("<p>a</p><p>b</p>", 1) == []
+        assert safe_split_points("plain text without markup", 4) == []
+        # Restarting before U+FEFF would drop it as a BOM
+        assert safe_split_points("<b>\ufeff<i>x", 10) == [len("<b>\ufeff<i>")]
+
+    def test_text_content(self):
+        html = (
+            "<!DOCTYPE html><title>A &lt; B</title><!-- note -->"
+            '<script>var s = "<p>x</p>";</script><style>p {}</style><p>Fish &amp; chips</p>'
+        )
+        assert text_content(html) == "A < BFish & chips"
+
+    def test_text_content_skips_only_until_matching_end_tag(self):
+        html = "<script>a</script>b<style>c</style>d"
+        assert text_content(html) == "bd"
+
+    def test_text_content_bytes(self):
+        assert text_content(b"<meta charset=utf-8><p>caf\xc3\xa9</p>") == "caf\u00e9"
+
+    def test_text_content_collapse_whitespace(self):
+        html = "\n  <p>a    b\n\tc</p>  <p>d&#9;e</p>\n"
+        assert text_content(html) == "\n  a    b\n\tc  d\te\n"
+        assert text_content(html, collapse_whitespace=True) == "a b c d e"
+
+    def test_tag_balance(self):
+        assert tag_balance("<div><p></p></div>") == {"div": 0, "p": 0}
+        assert tag_balance("<div><p></div>") == {"div": 0, "p": 1}
+        assert tag_balance("</b><i>") == {"b": -1, "i": 1}
+
+    def test_tag_balance_ignores_void_self_closing_and_raw_text(self):
+        html = "<br><img/></br><div/><svg><path/></svg><script><p></script><title></b></title>"
+        assert tag_balance(html) == {"div": 0, "svg": 0, "path": 0, "script": 0, "title": 0}
+        assert tag_balance(b"<ul><li>") == {"ul": 1, "li": 1}
+        assert tag_balance("") == {}
+
+    def test_tag_balance_custom_void_elements(self):
+        html = "<img><br><img></img><source>"
+        assert tag_balance(html) == {}
+        assert tag_balance(html, void_elements={"BR", "source"}) == {"img": 1}
+        assert tag_balance(html, void_elements=()) == {"img": 1, "br": 1, "source": 1}
+
+    def test_events_with_source(self):
+        html = '<!DOCTYPE html><p class="a">x &amp; y<!-- c --><br/></P>tail'
+        pairs = events_with_source(html)
+        assert pairs == [
+            ("doctype", ("html", None, None), "<!DOCTYPE html>"),
+            ("start", ("p", {"class": "a"}), '<p class="a">'),
+            ("text", "x & y", "x &amp; y"),
+            ("comment", " c ", "<!-- c -->"),
+            ("start", ("br", {}), "<br/>"),
+            ("end", "p", "</P>"),
+            ("text", "tail", "tail"),
+        ]
+        assert "".join(source for _, _, source in pairs) == html
+
+    def test_events_with_source_leaves_out_dropped_markup(self):
+        assert events_with_source("a<div class=x") == [("text", "a", "a")]
+        assert events_with_source("a</>b<p>") == [("text", "a", "a"), ("text", "b", "b"), ("start", ("p", {}), "<p>")]
+
+    def test_events_with_source_uses_normalized_input(self):
+        pairs = events_with_source(b"\xef\xbb\xbfa\r\n<b>")
+        assert pairs == [("text", "a\n", "a\n"), ("start", ("b", {}), "<b>")]
+
+    def test_tokenizer_errors(self):
+        html = "<p a=1 a=2><3\n<!-- open"
+        assert tokenizer_errors(html) == [
+            (10, "duplicate-attribute"),
+            (12, "invalid-first-character-of-tag-name"),
+            (22, "eof-in-comment"),
+        ]
+        assert tokenizer_errors("a\0b\n\0") == [(1, "unexpected-null-character"), (4, "unexpected-null-character")]
+        assert tokenizer_errors(b"\xef\xbb\xbf</>") == [(2, "empty-end-tag")]
+        assert tokenizer_errors("<p>fine</p>") == []
@@ -0,0 +1,625 @@
+import json
+import pickle
+import unittest
+from unittest import mock
+
+from justhtml import JustHTML, has_markup
+from justhtml.tokenizer import Tokenizer, TokenizerOpts
+from justhtml.tokens import CharacterTokens, CommentToken, EOFToken, Tag, token_from_dict
+from justhtml.treebuilder import TreeBuilder
+
+
+class _Node:
+    namespace = "html"
+
+
+class _RecordingSink:
+    """Records emitted tokens. Tag and comment tokens are reused by the tokenizer, so copy them."""
+
+    def __init__(self):
+        self.tokens = []
+        self.open_elements = [_Node()]
+
+    def process_token(self, token):
+        if isinstance(token, Tag):
+            raw_attrs = token.raw_attrs
+            token = Tag(token.kind, token.name, dict(token.attrs), token.self_closing, token.start_pos, token.end_pos)
+            token.raw_attrs = raw_attrs
+        elif isinstance(token, CommentToken):
+            token = CommentToken(token.data, token.start_pos)
+        self.tokens.append(token)
+        return 0
+
+    def process_characters(self, data):
+        self.tokens.append(CharacterTokens(data))
+
+
+class _AckRecordingTreeBuilder(TreeBuilder):
+    """Records (name, self_closing, acknowledged_self_closing) for each start tag after tree construction."""
+
+    def __init__(self):
+        super().__init__()
+        self.start_tags = []
+
+    def process_token(self, token):
+        result = super().process_token(token)
+        if isinstance(token, Tag) and token.kind == Tag.START:
+            self.start_tags.append((token.name, token.self_closing, token.acknowledged_self_closing))
+        return result
+
+
+def _tokenize(html, opts=None, **kwargs):
+    sink = _RecordingSink()
+    tokenizer = Tokenizer(sink, opts or TokenizerOpts(), **kwargs)
+    tokenizer.run(html)
+    return sink.tokens, tokenizer
+
+
+class TestTokenizer(unittest.TestCase):
+    def test_huge_attribute_value_skips_entity_decoding(self):
+        # A multi-megabyte value without '&' is sliced from the input once and never decoded.
+        value = "a" * 5_000_000
+        for html in (f'<img src="{value}">', f"<img src='{value}'>", f"<img src={value}>"):
+            with self.subTest(html=html[:12]):
+                with mock.patch("justhtml.tokenizer.decode_entities_in_text", side_effect=AssertionError):
+                    tokens, _ = _tokenize(html)
+                assert isinstance(tokens[0], Tag)
+                assert tokens[0].attrs["src"] == value
+                assert isinstance(tokens[-1], EOFToken)
+
+    def test_huge_attribute_value_with_entity_is_decoded(self):
+        value = "a" * 5_000_000
+        tokens, _ = _tokenize(f'<img src="{value}&amp;">')
+        assert tokens[0].attrs["src"] == value + "&"
+
+    def test_noncharacters_pass_through(self):
+        html = "<p title=\uffff \U0001fffe=x>a\ufffe<title>\U0010ffff</title><style>\ufdd0</style><!--\uffff-->"
+        tokens, _ = _tokenize(html)
+        assert tokens[0].attrs == {"title": "\uffff", "\U0001fffe": "x"}
+        assert [token.data for token in tokens if isinstance(token, (CharacterTokens, CommentToken))] == [
+            "a\ufffe",
+            "\U0010ffff",
+            "\ufdd0",
+            "\uffff",
+        ]
+
+    def test_max_attributes(self):
+        attrs = " ".join(f"a{i}={i}" for i in range(5000))
+        opts = TokenizerOpts(max_attributes=100)
+        tokens, tokenizer = _tokenize(f"<div {attrs} a0=dup>x</div>", opts, collect_errors=True)
+        assert tokens[0].attrs == {f"a{i}": str(i) for i in range(100)}
+        assert [token.data for token in tokens if isinstance(token, CharacterTokens)] == ["x"]
+        assert tokens[2].name == "div"
+        codes = [error.code for error in tokenizer.errors]
+        assert codes == ["too-many-attributes"] * 4900 + ["duplicate-attribute"]
+
+    def test_max_attributes_zero_and_unlimited(self):
+        tokens, _ = _tokenize("<p a b c>", TokenizerOpts(max_attributes=0))
+        assert tokens[0].attrs == {}
+        tokens, _ = _tokenize("<p a b c>")
+        assert list(tokens[0].attrs) == ["a", "b", "c"]
+
+    def test_xml_cdata(self):
+        html = "<p><![CDATA[<b>not a tag</b>]]></p>"
+        tokens, tokenizer = _tokenize(html, TokenizerOpts(xml_cdata=True), collect_errors=True)
+        assert [type(token) for token in tokens] == [Tag, CharacterTokens, Tag, EOFToken]
+        assert tokens[1].data == "<b>not a tag</b>"
+        assert tokenizer.errors == []
+        tokens, _ = _tokenize(html)
+        assert isinstance(tokens[1], CommentToken)
+        assert tokens[1].data == "[CDATA[<b"
+
+    def test_max_comment_length(self):
+        body = "a-b" * 100_000
+        opts = TokenizerOpts(max_comment_length=1024)
+        tokens, tokenizer = _tokenize(f"<!--{'x' * 10_000_000}--><!--{body}--><p>after", opts, collect_errors=True)
+        assert [token.data for token in tokens[:2]] == ["x" * 1024, body[:1024]]
+        assert tokens[2].name == "p"
+        assert tokens[3].data == "after"
+        assert [error.code for error in tokenizer.errors] == ["comment-too-long"] * 2
+
+    def test_max_comment_length_bounds_the_comment_buffer(self):
+        buffered = []
+
+        class _BufferRecordingTokenizer(Tokenizer):
+            def _emit_comment(self):
+                buffered.append(sum(len(part) for part in self.current_comment))
+                super()._emit_comment()
+
+        bodies = ["x" * 100_000, "-a" * 50_000, "--!a" * 25_000, "a\0" * 50_000]
+        html = f"<?{bodies[0]}><!{bodies[1]}>" + "".join(f"<!--{body}-->" for body in bodies) + "<p>"
+        sink = _RecordingSink()
+        opts = TokenizerOpts(max_comment_length=16, invalid_char_handler=lambda char: "?")
+        _BufferRecordingTokenizer(sink, opts).run(html)
+        assert buffered == [17] * 6
+        assert [len(token.data) for token in sink.tokens[:6]] == [16] * 6
+        assert sink.tokens[6].name == "p"
+
+    def test_max_comment_length_keeps_short_comments(self):
+        opts = TokenizerOpts(max_comment_length=3)
+        tokens, tokenizer = _tokenize("<!--abc--><!abcd>", opts, collect_errors=True)
+        assert [token.data for token in tokens[:2]] == ["abc", "abc"]
+        assert [error.code for error in tokenizer.errors] == ["incorrectly-opened-comment", "comment-too-long"]
+
+    def test_reset_to_data_discards_buffered_text(self):
+        sink = _RecordingSink()
+        tokenizer = Tokenizer(sink)
+        tokenizer.initialize("<script>discarded<<b>bold</b>")
+        while not (tokenizer.in_text_mode() and tokenizer.text_buffer):
+            tokenizer.step()
+        tokenizer.reset_to_data()
+        assert tokenizer.content_model() == "DATA"
+        while not tokenizer.step():
+            pass
+        assert [token.debug_str() for token in sink.tokens] == [
+            "StartTag(script, 0 attrs, self_closing=False)",
+            "StartTag(b, 0 attrs, self_closing=False)",
+            "Characters('bold')",
+            "EndTag(b)",
+            "EOF",
+        ]
+
+    def test_reset_to_data_rereads_reconsumed_character(self):
+        cases = [
+            ("<title>x</tit<i>y", ["StartTag(i, 0 attrs, self_closing=False)", "Characters('y')", "EOF"]),
+            ("<title>x</", ["EOF"]),
+        ]
+        for html, expected in cases:
+            with self.subTest(html=html):
+                sink = _RecordingSink()
+                tokenizer = Tokenizer(sink)
+                tokenizer.initialize(html)
+                while not tokenizer.reconsume:
+                    tokenizer.step()
+                tokenizer.reset_to_data()
+                while not tokenizer.step():
+                    pass
+                assert [token.debug_str() for token in sink.tokens[1:]] == expected
+
+    def test_require_semicolon(self):
+        html = '<p title="&amp &amp; &#60 &#x3c;">&amp &amp; &#60 &#60; &notit; &foo;'
+        tokens, _ = _tokenize(html, TokenizerOpts(require_semicolon=True))
+        assert tokens[0].attrs == {"title": "&amp & &#60 <"}
+        assert tokens[1].data == "&amp & &#60 < &notit; &foo;"
+        tokens, _ = _tokenize(html)
+        assert tokens[0].attrs == {"title": "& & < <"}
+        assert tokens[1].data == "& & < < \u00acit; &foo;"
+
+    def test_raw_passthrough_tags(self):
+        html = "<my-widget><b>not parsed</b></my-widget-x></MY-WIDGET><b>parsed</b>"
+        tokens, _ = _tokenize(html, TokenizerOpts(raw_passthrough_tags=["My-Widget"]))
+        assert [type(token) for token in tokens] == [Tag, CharacterTokens, Tag, Tag, CharacterTokens, Tag, EOFToken]
+        assert tokens[1].data == "<b>not parsed</b></my-widget-x>"
+        assert (tokens[2].kind, tokens[2].name) == (Tag.END, "my-widget")
+        assert tokens[3].name == "b"
+        tokens, _ = _tokenize(html)
+        assert tokens[1].name == "b"
+
+    def test_split_on_newline_in_plaintext(self):
+        html = "<plaintext>one\n<b>two</b>\n\x00three"
+        tokens, tokenizer = _tokenize(html, TokenizerOpts(split_on_newline=True), collect_errors=True)
+        assert [token.data for token in tokens[1:-1]] == ["one\n", "<b>two</b>\n", "\ufffdthree"]
+        assert [error.code for error in tokenizer.errors] == ["unexpected-null-character"]
+        tokens, _ = _tokenize(html)
+        assert [token.data for token in tokens[1:-1]] == ["one\n<b>two</b>\n\ufffdthree"]
+
+    def test_strip_leading_newline(self):
+        opts = TokenizerOpts(strip_leading_newline=True)
+        tokens, _ = _tokenize("<textarea>\nabc</textarea><textarea>\n\nabc</textarea><pre>\r\nx</pre>", opts)
+        assert [token.data for token in tokens if isinstance(token, CharacterTokens)] == ["abc", "\nabc", "x"]
+        tokens, _ = _tokenize("<listing>&#10;x</listing><pre>\n</pre><pre><b>\nx</b></pre><div>\ny", opts)
+        assert [token.data for token in tokens if isinstance(token, CharacterTokens)] == ["x", "\nx", "\ny"]
+        tokens, _ = _tokenize("<textarea>\nabc</textarea><pre>\nx")
+        assert [token.data for token in tokens if isinstance(token, CharacterTokens)] == ["\nabc", "\nx"]
+
+    def test_unclosed_rawtext(self):
+        for html, expected in [
+            ("<script>alert(1)", "script"),
+            ("<style>body{}", "style"),
+            ("<title>x</title", "title"),
+            ("<script><!--<script>x", "script"),
+            ("<script>alert(1)</script>", None),
+            ("<plaintext>x", None),
+            ("<p>x", None),
+        ]:
+            with self.subTest(html=html):
+                _, tokenizer = _tokenize(html)
+                assert tokenizer.unclosed_rawtext() == expected
+
+    def test_unclosed_rawtext_before_eof(self):
+        tokenizer = Tokenizer(_RecordingSink())
+        tokenizer.initialize("<script>alert(1)</script>")
+        while not tokenizer.in_text_mode():
+            tokenizer.step()
+        assert tokenizer.unclosed_rawtext() is None
+
+    def test_end_tags_drop_attributes_and_self_closing(self):
+        tokens, tokenizer = _tokenize("<p></p a=1><div></div/>", collect_errors=True)
+        end_tags = [token for token in tokens if isinstance(token, Tag) and token.kind == Tag.END]
+        assert [(tag.name, tag.attrs, tag.self_closing) for tag in end_tags] == [("p", {}, False), ("div", {}, False)]
+        assert end_tags[0].to_dict()["attrs"] == []
+        assert [error.code for error in tokenizer.errors] == [
+            "end-tag-with-attributes",
+            "end-tag-with-trailing-solidus",
+        ]
+
+    def test_is_foreign_root(self):
+        tokens, _ = _tokenize("<svg><math><div></svg><SVG viewBox=1>")
+        tags = [token for token in tokens if isinstance(token, Tag)]
+        assert [(tag.name, tag.is_foreign_root) for tag in tags] == [
+            ("svg", True),
+            ("math", True),
+            ("div", False),
+            ("svg", False),
+            ("svg", True),
+        ]
+        # Attribute names are lowercased here; the tree builder restores SVG casing (e.g. viewBox).
+        assert tags[-1].attrs == {"viewbox": "1"}
+
+    def test_start_and_end_tag_predicates(self):
+        tokens, _ = _tokenize("<p>a</p><br/></br>")
+        tags = [token for token in tokens if isinstance(token, Tag)]
+        assert [(tag.is_start_tag, tag.is_end_tag) for tag in tags] == [
+            (True, False),
+            (False, True),
+            (True, False),
+            (False, True),
+        ]
+
+    def test_serialize_attrs(self):
+        html = """<input type=checkbox checked disabled="" value='a"b' title="x&amp;y<z" hidden=HIDDEN>"""
+        tokens, _ = _tokenize(html + "<p>")
+        expected = """ type="checkbox" checked disabled value='a"b' title="x&amp;y<z" hidden"""
+        assert tokens[0].serialize_attrs() == expected
+        assert tokens[1].serialize_attrs() == ""
+
+    def test_debug_str(self):
+        tokens, _ = _tokenize('<div id=a class=b hidden/><br x=1></div><!--short-->' + "x" * 50)
+        assert tokens[0].debug_str() == "StartTag(div, 3 attrs, self_closing=True)"
+        assert tokens[1].debug_str() == "StartTag(br, 1 attr, self_closing=False)"
+        assert tokens[2].debug_str() == "EndTag(div)"
+        assert tokens[3].debug_str() == "Comment('short')"
+        assert tokens[4].debug_str() == "Characters('xxxxxxxxxxxxxxxxxxxx'... len=50)"
+        assert tokens[4].debug_str(max_len=5) == "Characters('xxxxx'... len=50)"
+        assert tokens[5].debug_str() == "EOF"
+        assert repr(tokens[2]) == "EndTag(div)"
+
+    def test_debug_str_doctype(self):
+        tokens, _ = _tokenize('<!DOCTYPE html PUBLIC "-//W3C//DTD HTML 4.01//EN" "about:legacy-compat"><!DOCTYPE>')
+        assert tokens[0].debug_str(max_len=10) == (
+            "Doctype(html, public='-//W3C//DT'... len=25, system='about:lega'... len=19)"
+        )
+        assert tokens[1].debug_str() == "Doctype(None, force_quirks=True)"
+
+    def test_doctype_quirks_mode(self):
+        cases = [
+            ("<!DOCTYPE html>", "no-quirks"),
+            ('<!DOCTYPE html PUBLIC "-//W3C//DTD HTML 4.0 Transitional//EN">', "quirks"),
+            ('<!DOCTYPE html PUBLIC "-//W3C//DTD XHTML 1.0 Frameset//EN" "x.dtd">', "limited-quirks"),
+            ('<!DOCTYPE html PUBLIC "-//W3C//DTD HTML 4.01 Transitional//EN">', "quirks"),
+            ('<!DOCTYPE html PUBLIC "-//W3C//DTD HTML 4.01 Transitional//EN" "x.dtd">', "limited-quirks"),
+            ("<!DOCTYPE svg>", "quirks"),
+            ("<!DOCTYPE html", "quirks"),
+        ]
+        for html, expected in cases:
+            with self.subTest(html=html):
+                tokens, _ = _tokenize(html)
+                assert tokens[0].doctype.quirks_mode() == expected
+
+    def test_to_dict_round_trip(self):
+        html = '<!DOCTYPE html PUBLIC "p" "s"><p b=2 a=1 hidden/>x<!--c--></p>'
+        opts = TokenizerOpts(capture_raw_attrs=True)
+        tokens, _ = _tokenize(html, opts, track_tag_positions=True)
+        dicts = [token.to_dict() for token in tokens]
+        assert dicts[1] == {
+            "type": "StartTag",
+            "name": "p",
+            "attrs": [["b", "2"], ["a", "1"], ["hidden", ""]],
+            "self_closing": True,
+            "acknowledged_self_closing": False,
+            "raw_attrs": " b=2 a=1 hidden/",
+            "start_pos": 30,
+            "end_pos": 49,
+        }
+        restored = [token_from_dict(data) for data in json.loads(json.dumps(dicts))]
+        assert [type(token) for token in restored] == [type(token) for token in tokens]
+        assert [token.to_dict() for token in restored] == dicts
+        assert list(restored[1].attrs) == ["b", "a", "hidden"]
+        assert [token.to_dict()["type"] for token in restored] == [
+            "Doctype",
+            "StartTag",
+            "Characters",
+            "Comment",
+            "EndTag",
+            "EOF",
+        ]
+
+    def test_pickle_round_trip(self):
+        html = '<!DOCTYPE html SYSTEM "s"><p b=2 a=1 hidden/>x<!--c--></p>'
+        tokens, _ = _tokenize(html, TokenizerOpts(capture_raw_attrs=True), track_tag_positions=True)
+        restored = pickle.loads(pickle.dumps(tokens, protocol=pickle.HIGHEST_PROTOCOL))
+        assert [type(token) for token in restored] == [type(token) for token in tokens]
+        assert [token.to_dict() for token in restored] == [token.to_dict() for token in tokens]
+
+    def test_from_dict_rejects_unknown_type(self):
+        with self.assertRaises(ValueError):
+            token_from_dict({"type": "Text"})
+
+    def test_acknowledged_self_closing(self):
+        builder = _AckRecordingTreeBuilder()
+        JustHTML("<br/><div/><image/><svg><path/></svg><math/><p>", tree_builder=builder)
+        assert builder.start_tags == [
+            ("br", True, True),
+            ("div", True, False),
+            ("image", True, True),
+            ("svg", False, False),
+            ("path", True, True),
+            ("math", True, True),
+            ("p", False, False),
+        ]
+
+    def test_acknowledged_self_closing_for_popped_void_elements(self):
+        builder = _AckRecordingTreeBuilder()
+        JustHTML("<table><colgroup><col/></colgroup><input type='hidden'/></table>", tree_builder=builder)
+        assert builder.start_tags == [
+            ("table", False, False),
+            ("colgroup", False, False),
+            ("col", True, True),
+            ("input", True, True),
+        ]
+
+        builder = _AckRecordingTreeBuilder()
+        JustHTML("<frameset><frame/><frame></frameset>", tree_builder=builder)
+        assert builder.start_tags == [
+            ("frameset", False, False),
+            ("frame", True, True),
+            ("frame", False, False),
+        ]
+
+    def test_acknowledged_self_closing_is_settable(self):
+        tag = Tag(Tag.START, "x", {}, True)
+        assert tag.acknowledged_self_closing is False
+        tag.acknowledged_self_closing = True
+        assert tag.acknowledged_self_closing is True
+
+    def test_content_model(self):
+        cases = [
+            ("<div>", "DATA"),
+            ("<title>", "RCDATA"),
+            ("<textarea>", "RCDATA"),
+            ("<style>", "RAWTEXT"),
+            ("<script>", "RAWTEXT"),
+            ("<script><!--", "RAWTEXT"),
+            ("<plaintext>", "PLAINTEXT"),
+            # noembed and noframes are raw text whatever the scripting flag
+            ("<noembed>", "RAWTEXT"),
+            ("<noframes>", "RAWTEXT"),
+            # Parsing runs with scripting disabled, so noscript content is markup
+            ("<noscript>", "DATA"),
+        ]
+        for html, expected in cases:
+            with self.subTest(html=html):
+                sink = _RecordingSink()
+                tokenizer = Tokenizer(sink)
+                tokenizer.initialize(html + "x")
+                assert tokenizer.content_model() == "DATA"
+                assert tokenizer.in_text_mode() is False
+                while tokenizer.pos < len(html):
+                    tokenizer.step()
+                assert tokenizer.content_model() == expected
+                assert tokenizer.in_text_mode() is (expected != "DATA")
+
+    def test_content_model_returns_to_data(self):
+        sink = _RecordingSink()
+        tokenizer = Tokenizer(sink)
+        tokenizer.initialize("<style>a</style>b")
+        while tokenizer.pos < len("<style>a</style>"):
+            tokenizer.step()
+        assert tokenizer.content_model() == "DATA"
+
+    def test_set_content_model(self):
+        # Expected tokens are text data or (kind, name) for tags
+        cases = [
+            ("DATA", None, "<b>x</b>", [(Tag.START, "b"), "x", (Tag.END, "b")]),
+            ("RCDATA", "title", "<b>&amp;</title>x", ["<b>&", (Tag.END, "title"), "x"]),
+            ("RAWTEXT", "style", "<b>&amp;</style>", ["<b>&amp;", (Tag.END, "style")]),
+            ("RAWTEXT", "script", "<!--</script>-->", ["<!--", (Tag.END, "script"), "-->"]),
+            ("PLAINTEXT", None, "<b></plaintext>", ["<b></plaintext>"]),
+        ]
+        for name, tag_name, html, expected in cases:
+            with self.subTest(name=name, tag_name=tag_name):
+                sink = _RecordingSink()
+                tokenizer = Tokenizer(sink)
+                tokenizer.initialize(html)
+                tokenizer.set_content_model(name, tag_name)
+                assert tokenizer.content_model() == name
+                while not tokenizer.step():
+                    pass
+                assert [
+                    token.data if isinstance(token, CharacterTokens) else (token.kind, token.name)
+                    for token in sink.tokens
+                    if not isinstance(token, EOFToken)
+                ] == expected
+
+    def test_entity_decoding_follows_content_model(self):
+        # Decoding depends on the state, not on which element's end tag ends it
+        cases = [("RCDATA", "style", "a&amp;b</style>", "a&b"), ("RAWTEXT", "title", "a&amp;b</title>", "a&amp;b")]
+        for name, tag_name, html, expected in cases:
+            with self.subTest(name=name):
+                sink = _RecordingSink()
+                tokenizer = Tokenizer(sink)
+                tokenizer.initialize(html)
+                tokenizer.set_content_model(name, tag_name)
+                while not tokenizer.step():
+                    pass
+                assert sink.tokens[0].data == expected
+
+    def test_set_content_model_rejects_unknown_names(self):
+        tokenizer = Tokenizer(_RecordingSink())
+        tokenizer.initialize("")
+        for name in ("SCRIPT", "rcdata", "", "CDATA"):
+            with self.subTest(name=name), self.assertRaises(ValueError):
+                tokenizer.set_content_model(name)
+        assert tokenizer.content_model() == "DATA"
+
+    def test_plain_non_js_scripts(self):
+        opts = TokenizerOpts(plain_non_js_scripts=True)
+        body = "<!--<script></script>x</script>"
+        escaped = ["<!--<script></script>x", (Tag.END, "script")]
+        plain = ["<!--<script>", (Tag.END, "script"), "x", (Tag.END, "script")]
+        cases = [
+            ("", escaped),
+            (' type=""', escaped),
+            (' type=" Text/JavaScript "', escaped),
+            (" type=module", escaped),
+            (' language=""', escaped),
+            (" language=javascript", escaped),
+            (' type="text/html"', plain),
+            (" type=text/x-template", plain),
+            (' type="text/javascript; charset=utf-8"', plain),
+            (" language=vbscript", plain),
+        ]
+        for attrs, expected in cases:
+            with self.subTest(attrs=attrs):
+                tokens, _ = _tokenize(f"<script{attrs}>{body}", opts)
+                assert [
+                    token.data if isinstance(token, CharacterTokens) else (token.kind, token.name)
+                    for token in tokens[1:-1]
+                ] == expected
+
+    def test_plain_non_js_scripts_off_by_default(self):
+        tokens, _ = _tokenize('<script type="text/html"><!--<script></script>x</script>')
+        assert tokens[1].data == "<!--<script></script>x"
+
+    def test_raw_attrs(self):
+        opts = TokenizerOpts(capture_raw_attrs=True)
+        cases = [
+            ('<div   class = "x"  data-y >', '   class = "x"  data-y ', {"class": "x", "data-y": ""}),
+            ("<br/>", "/", {}),
+            ("<p>", "", {}),
+            ("</p >", " ", {}),
+            ("<A\nHREF=x>", "\nHREF=x", {"href": "x"}),
+            ("<img src=a/>", " src=a/", {"src": "a/"}),
+        ]
+        for html, raw, attrs in cases:
+            with self.subTest(html=html):
+                tokens, _ = _tokenize(html, opts)
+                assert tokens[0].raw_attrs == raw
+                assert tokens[0].attrs == attrs
+
+    def test_raw_attrs_rawtext_end_tags(self):
+        opts = TokenizerOpts(capture_raw_attrs=True)
+        tokens, _ = _tokenize("<title>t</title\tx=1><style>s</style>", opts)
+        tags = [token for token in tokens if isinstance(token, Tag)]
+        assert [tag.raw_attrs for tag in tags] == ["", "\tx=1", "", ""]
+
+    def test_collapse_whitespace(self):
+        html = "<p>a    b\n\tc</p><title> x &#9; y</title><style>a  b</style>"
+        tokens, _ = _tokenize(html, TokenizerOpts(collapse_whitespace=True))
+        texts = [token.data for token in tokens if isinstance(token, CharacterTokens)]
+        # RAWTEXT keeps its whitespace; character references are decoded before collapsing
+        assert texts == ["a b c", " x y", "a  b"]
+
+    def test_collapse_whitespace_off_by_default(self):
+        tokens, _ = _tokenize("<p>a    b\n\tc</p>")
+        assert tokens[1].data == "a    b\n\tc"
+
+    def test_raw_attrs_off_by_default(self):
+        tokens, _ = _tokenize('<p class="x">')
+        assert tokens[0].raw_attrs is None
+
+    def test_is_template(self):
+        tokens, _ = _tokenize("<template><script>x</script></template><TEMPLATE/>")
+        tags = [token for token in tokens if isinstance(token, Tag)]
+        assert [(tag.name, tag.is_template) for tag in tags] == [
+            ("template", True),
+            ("script", False),
+            ("script", False),
+            ("template", True),
+            ("template", True),
+        ]
+
+    def test_template_contents_tokenize_as_markup(self):
+        html = "<template><style>a<b></style><script><!--<p>--></script><p>&amp;</template>"
+        tokens, _ = _tokenize(html)
+        assert [token.debug_str(30) for token in tokens] == [
+            "StartTag(template, 0 attrs, self_closing=False)",
+            "StartTag(style, 0 attrs, self_closing=False)",
+            "Characters('a<b>')",
+            "EndTag(style)",
+            "StartTag(script, 0 attrs, self_closing=False)",
+            "Characters('<!--<p>-->')",
+            "EndTag(script)",
+            "StartTag(p, 0 attrs, self_closing=False)",
+            "Characters('&')",
+            "EndTag(template)",
+            "EOF",
+        ]
+
+    def test_template_start_tag_keeps_data_content_model(self):
+        sink = _RecordingSink()
+        tokenizer = Tokenizer(sink)
+        tokenizer.initialize("<template>x")
+        while not sink.tokens:
+            tokenizer.step()
+        assert sink.tokens[0].name == "template"
+        assert tokenizer.content_model() == "DATA"
+
+    def test_lang_and_direction(self):
+        tokens, _ = _tokenize('<html lang="en-US" dir="RTL"><p dir=Auto><bdo dir=sideways><b dir><i>')
+        assert (tokens[0].lang, tokens[0].direction) == ("en-US", "rtl")
+        assert (tokens[1].lang, tokens[1].direction) == (None, "auto")
+        assert tokens[2].direction is None
+        assert tokens[3].direction is None
+        assert tokens[4].direction is None
+
+    def test_meta_refresh(self):
+        cases = [
+            ("5; url=http://x", (5, "http://x")),
+            ("0", (0, None)),
+            (" 1.5 , URL = 'a b' c", (1, "a b")),
+            (".5 'x", (0, "x")),
+            ("3;", (3, None)),
+            ("2 url x", (2, "x")),
+            ("2; /next", (2, "/next")),
+            ("", None),
+            ("soon", None),
+            ("5x; url=y", None),
+        ]
+        for content, expected in cases:
+            with self.subTest(content=content):
+                tokens, _ = _tokenize(f'<meta http-equiv="Refresh" content="{content}">')
+                assert tokens[0].meta_refresh() == expected
+        tokens, _ = _tokenize('<meta http-equiv=refresh><meta http-equiv=expires content=0><meta content=0><p></meta>')
+        assert [token.meta_refresh() for token in tokens[:5]] == [None] * 5
+
+    def test_attrs_equal_and_diff(self):
+        tokens, _ = _tokenize("<a href=x class=y><a class=y href=x><a href=z><a href=z id>")
+        first, reordered, changed, added = tokens[:4]
+        assert first.attrs_equal(reordered)
+        assert first.attrs_diff(reordered) == ({}, {}, {})
+        assert not first.attrs_equal(changed)
+        assert first.attrs_diff(changed) == ({}, {"class": "y"}, {"href": ("x", "z")})
+        assert changed.attrs_diff(added) == ({"id": ""}, {}, {})
+
+    def test_has_markup(self):
+        assert has_markup("plain text") is False
+        assert has_markup("a < b") is False
+        assert has_markup("1 <2") is False
+        assert has_markup("") is False
+        for html in ("<p>", "x <B>", "<!-- c -->", "</p>", "<?xml?>", "<!DOCTYPE html>"):
+            with self.subTest(html=html):
+                assert has_markup(html) is True
+
+    def test_noscript_content_is_markup_in_head_and_body(self):
+        cases = [("<head><noscript><link rel=x></noscript>", "link"), ("<body><noscript><p>x</p></noscript>", "p")]
+        for html, child in cases:
+            with self.subTest(html=html):
+                noscript = JustHTML(html).query("noscript")[0]
+                assert [node.name for node in noscript.children] == [child]
+
+    def test_null_in_tag_name_is_an_unknown_element(self):
+        body = JustHTML("<di\0v><p>x</p></di\0v>y").query("body")[0]
+        assert [node.name for node in body.children] == ["di\ufffdv", "#text"]
+        assert body.children[0].namespace == "html"
+        assert [node.name for node in body.children[0].children] == ["p"]